@@ -1,99 +1,203 @@
-use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Itertools;
-use std::collections::VecDeque;
+use aoc_runner_derive::aoc;
 
-#[aoc_generator(day10)]
-fn parse_input(input: &str) -> Vec<VecDeque<char>> {
-    input
-        .lines()
-        .map(|line| line.chars().collect::<VecDeque<_>>())
-        .collect::<Vec<_>>()
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Delimiter<'a> {
+    pub open: &'a str,
+    pub close: &'a str,
+    /// Whether other chunks can be opened inside of this one. Chunks that
+    /// can't, like strings, ignore everything up to their closing delimiter.
+    pub nested: bool,
+    /// Points for a corrupted line where this closing delimiter was found.
+    pub syntax_error_score: u64,
+    /// Points for each of these closing delimiters missing from a line.
+    pub completion_score: u64,
 }
 
-#[aoc(day10, part1)]
-fn solve_part_1(input: &[VecDeque<char>]) -> u32 {
-    let mut stack = VecDeque::new();
-    let mut points = 0;
-
-    for line in input {
-        let result = line.iter().fold(0, |mut acc, item| {
-            match item {
-                '<' | '(' | '[' | '{' => stack.push_back(item),
-                '}' | ']' | ')' | '>' => {
-                    let last_item = *stack.pop_back().unwrap();
-
-                    acc += match item {
-                        '}' if last_item != '{' => 1197,
-                        ']' if last_item != '[' => 57,
-                        ')' if last_item != '(' => 3,
-                        '>' if last_item != '<' => 25137,
-                        _ => 0,
-                    };
-                }
-                _ => unreachable!("Invalid character: {}", item),
-            }
+impl<'a> Delimiter<'a> {
+    /// Panics if either token is empty, since an empty token would match
+    /// everywhere.
+    pub const fn new(
+        open: &'a str, close: &'a str, syntax_error_score: u64,
+        completion_score: u64,
+    ) -> Self {
+        assert!(!open.is_empty(), "the open token must not be empty");
+        assert!(!close.is_empty(), "the close token must not be empty");
 
-            acc
-        });
+        Self {
+            open,
+            close,
+            nested: true,
+            syntax_error_score,
+            completion_score,
+        }
+    }
 
-        points += result;
+    /// A chunk whose content isn't checked, like a quoted string.
+    pub const fn verbatim(
+        open: &'a str, close: &'a str, syntax_error_score: u64,
+        completion_score: u64,
+    ) -> Self {
+        Self {
+            nested: false,
+            ..Self::new(open, close, syntax_error_score, completion_score)
+        }
     }
+}
 
-    points
+#[derive(Copy, Clone, Debug)]
+pub struct DelimiterSet<'a> {
+    pub delimiters: &'a [Delimiter<'a>],
 }
 
-#[aoc(day10, part2)]
-fn solve_part_2(input: &[VecDeque<char>]) -> u64 {
-    let mut stack = VecDeque::new();
-    let mut scores = Vec::new();
-
-    for line in input {
-        let result = line.iter().fold(0, |mut acc, item| {
-            match item {
-                '<' | '(' | '[' | '{' => stack.push_back(item),
-                '}' | ']' | ')' | '>' => {
-                    let last_item = *stack.pop_back().unwrap();
-
-                    acc += match item {
-                        '}' if last_item != '{' => 1197,
-                        ']' if last_item != '[' => 57,
-                        ')' if last_item != '(' => 3,
-                        '>' if last_item != '<' => 25137,
-                        _ => 0,
-                    };
-                }
-                _ => unreachable!("Invalid character"),
-            }
+/// The brackets and scores used by the puzzle.
+pub const PUZZLE_DELIMITERS: DelimiterSet<'static> = DelimiterSet {
+    delimiters: &[
+        Delimiter::new("(", ")", 3, 1),
+        Delimiter::new("[", "]", 57, 2),
+        Delimiter::new("{", "}", 1197, 3),
+        Delimiter::new("<", ">", 25137, 4),
+    ],
+};
 
-            acc
-        });
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineStatus {
+    Valid,
+    /// The token at `position` (in characters) doesn't close the last open
+    /// chunk. `expected` is `None` if there was no chunk left to close.
+    Corrupted {
+        position: usize,
+        expected: Option<String>,
+        found: String,
+    },
+    /// The line ends with open chunks, which are closed by `completion`.
+    /// `missing` are the indices of their delimiters, innermost first.
+    Incomplete {
+        completion: String,
+        missing: Vec<usize>,
+    },
+}
+
+impl<'a> DelimiterSet<'a> {
+    /// The delimiter with the longest token matching the start of `text`.
+    /// Delimiters with an empty token never match, as they wouldn't advance
+    /// the line. This way they are never opened either, so their empty
+    /// closing token is never checked.
+    fn longest_match<F>(&self, text: &str, token: F) -> Option<usize>
+    where F: Fn(&Delimiter<'a>) -> &'a str {
+        (0..self.delimiters.len())
+            .filter(|&i| {
+                let delimiter = &self.delimiters[i];
+                !delimiter.open.is_empty()
+                    && !delimiter.close.is_empty()
+                    && text.starts_with(token(delimiter))
+            })
+            .max_by_key(|&i| token(&self.delimiters[i]).len())
+    }
 
-        if result == 0 {
-            let mut points = 0;
-            for item in stack.iter().rev() {
-                points *= 5;
-                points += match item {
-                    '(' => 1,
-                    '[' => 2,
-                    '{' => 3,
-                    '<' => 4,
-                    _ => unreachable!(),
+    pub fn check_line(&self, line: &str) -> LineStatus {
+        let mut stack: Vec<usize> = Vec::new();
+        let mut offset = 0;
+
+        while offset < line.len() {
+            let rest = &line[offset..];
+
+            // The closing delimiter takes precedence, so that delimiters that
+            // open and close with the same token work.
+            //
+            if let Some(&last) = stack.last() {
+                let delimiter = &self.delimiters[last];
+                if rest.starts_with(delimiter.close) {
+                    stack.pop();
+                    offset += delimiter.close.len();
+                    continue;
+                }
+
+                if !delimiter.nested {
+                    offset += rest.chars().next().unwrap().len_utf8();
+                    continue;
                 }
             }
 
-            println!(
-                "Missing elements: {:?} - {:?} - {:?}",
-                line.iter().join(""),
-                stack.iter().join(""),
-                points
-            );
+            if let Some(index) = self.longest_match(rest, |d| d.open) {
+                stack.push(index);
+                offset += self.delimiters[index].open.len();
+                continue;
+            }
 
-            scores.push(points);
+            let found = match self.longest_match(rest, |d| d.close) {
+                Some(index) => self.delimiters[index].close.to_string(),
+                None => rest.chars().next().unwrap().to_string(),
+            };
+            return LineStatus::Corrupted {
+                position: line[..offset].chars().count(),
+                expected: stack
+                    .last()
+                    .map(|&last| self.delimiters[last].close.to_string()),
+                found,
+            };
         }
 
-        stack.clear();
+        if stack.is_empty() {
+            return LineStatus::Valid;
+        }
+
+        stack.reverse();
+        LineStatus::Incomplete {
+            completion: stack
+                .iter()
+                .map(|&i| self.delimiters[i].close)
+                .collect(),
+            missing: stack,
+        }
     }
 
+    /// The score of a corrupted line, or 0 for all other lines.
+    pub fn syntax_error_score(&self, status: &LineStatus) -> u64 {
+        match status {
+            LineStatus::Corrupted { found, .. } => self
+                .delimiters
+                .iter()
+                .find(|d| d.close == found)
+                .map_or(0, |d| d.syntax_error_score),
+            _ => 0,
+        }
+    }
+
+    /// The score of an incomplete line.
+    pub fn completion_score(&self, status: &LineStatus) -> Option<u64> {
+        match status {
+            LineStatus::Incomplete { missing, .. } => {
+                Some(missing.iter().fold(0, |points, &i| {
+                    points * 5 + self.delimiters[i].completion_score
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Checks the line with the [`PUZZLE_DELIMITERS`].
+pub fn check_line(line: &str) -> LineStatus {
+    PUZZLE_DELIMITERS.check_line(line)
+}
+
+#[aoc(day10, part1)]
+fn solve_part_1(input: &str) -> u64 {
+    input
+        .lines()
+        .map(|line| PUZZLE_DELIMITERS.syntax_error_score(&check_line(line)))
+        .sum()
+}
+
+#[aoc(day10, part2)]
+fn solve_part_2(input: &str) -> u64 {
+    let mut scores = input
+        .lines()
+        .filter_map(|line| {
+            PUZZLE_DELIMITERS.completion_score(&check_line(line))
+        })
+        .collect::<Vec<u64>>();
+
     scores.sort_unstable();
 
     scores[scores.len() / 2]
@@ -108,13 +212,118 @@ mod tests {
         "[({(<(())[]>[[{[]{<()<>>\n[(()[<>])]({[<{<<[]>>(\n{([(<{}[<>[]}>{[]{[(<()>\n(((({<>}<{<{<>}{[]{[]{}\n[[<[([]))<([[{}[[()]]]\n[{[{({}]{}}([{[{{{}}([]\n{<[[]]>}<{[{[{[]{()[[[]\n[<(<(<(<{}))><([]([]()\n<{([([[(<>()){}]>(<<{{\n<{([{{}}[<[[[<>{}]]]>[]]"
     }
 
+    #[test]
+    fn test_check_line() {
+        assert_eq!(check_line("([]){<>}"), LineStatus::Valid);
+        assert_eq!(
+            check_line("{([(<{}[<>[]}>{[]{[(<()>"),
+            LineStatus::Corrupted {
+                position: 12,
+                expected: Some("]".to_string()),
+                found: "}".to_string()
+            }
+        );
+        assert_eq!(check_line("())"), LineStatus::Corrupted {
+            position: 2,
+            expected: None,
+            found: ")".to_string()
+        });
+        assert_eq!(check_line("(a)"), LineStatus::Corrupted {
+            position: 1,
+            expected: Some(")".to_string()),
+            found: "a".to_string()
+        });
+        assert_eq!(
+            check_line("[({(<(())[]>[[{[]{<()<>>"),
+            LineStatus::Incomplete {
+                completion: "}}]])})]".to_string(),
+                missing: vec![2, 2, 1, 1, 0, 2, 0, 1]
+            }
+        );
+    }
+
+    #[test]
+    fn test_custom_delimiters() {
+        let delimiters = [
+            Delimiter::new("(", ")", 1, 1),
+            Delimiter::new("begin", "end", 10, 2),
+            Delimiter::verbatim("\"", "\"", 100, 3),
+        ];
+        let delimiters = DelimiterSet {
+            delimiters: &delimiters,
+        };
+
+        assert_eq!(
+            delimiters.check_line("begin\"end ( \"()end"),
+            LineStatus::Valid
+        );
+
+        let status = delimiters.check_line("begin(end)");
+        assert_eq!(status, LineStatus::Corrupted {
+            position: 6,
+            expected: Some(")".to_string()),
+            found: "end".to_string()
+        });
+        assert_eq!(delimiters.syntax_error_score(&status), 10);
+
+        let status = delimiters.check_line("(begin\"(");
+        assert_eq!(status, LineStatus::Incomplete {
+            completion: "\"end)".to_string(),
+            missing: vec![2, 1, 0]
+        });
+        assert_eq!(
+            delimiters.completion_score(&status),
+            Some(3 * 25 + 2 * 5 + 1)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be empty")]
+    fn test_empty_token() { Delimiter::verbatim("\"", "", 1, 1); }
+
+    #[test]
+    fn test_empty_token_literal() {
+        // Delimiters built without the constructors can still have empty
+        // tokens, which are ignored.
+        //
+        let delimiters = [
+            Delimiter::new("(", ")", 1, 1),
+            Delimiter {
+                open: "",
+                ..Delimiter::new("[", "]", 2, 2)
+            },
+            Delimiter {
+                close: "",
+                ..Delimiter::new("<", ">", 3, 3)
+            },
+        ];
+        let delimiters = DelimiterSet {
+            delimiters: &delimiters,
+        };
+
+        assert_eq!(delimiters.check_line("(())"), LineStatus::Valid);
+        assert_eq!(delimiters.check_line("(]"), LineStatus::Corrupted {
+            position: 1,
+            expected: Some(")".to_string()),
+            found: "]".to_string()
+        });
+        assert_eq!(delimiters.check_line("(<"), LineStatus::Corrupted {
+            position: 1,
+            expected: Some(")".to_string()),
+            found: "<".to_string()
+        });
+        assert_eq!(delimiters.check_line("(x)"), LineStatus::Corrupted {
+            position: 1,
+            expected: Some(")".to_string()),
+            found: "x".to_string()
+        });
+    }
+
     #[test]
     fn test_example() {
         let input = get_input();
 
-        let input = parse_input(input);
-
-        assert_eq!(solve_part_1(&input), 26397);
-        assert_eq!(solve_part_2(&input), 288957);
+        assert_eq!(solve_part_1(input), 26397);
+        assert_eq!(solve_part_2(input), 288957);
     }
 }
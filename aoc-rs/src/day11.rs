@@ -1,9 +1,20 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Itertools;
+use crate::{
+    error::ParseError,
+    grid::{Grid, Rgb, HIGHLIGHT_COLOUR},
+};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    io::Write,
+    path::Path,
+    thread,
+    time::Duration,
+};
 
 pub const THRESHOLD: u8 = 9;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Octopus {
     energy: u8,
     flashed: bool,
@@ -20,107 +31,77 @@ impl Octopus {
     pub fn charge(&mut self) { self.energy += 1; }
 }
 
+impl Display for Octopus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.energy)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Board {
-    fields: Vec<Vec<Octopus>>,
+    fields: Grid<Octopus>,
 }
 
 impl Board {
-    pub fn new(fields: Vec<Vec<Octopus>>) -> Self { Board { fields } }
+    pub fn new(fields: Grid<Octopus>) -> Self { Board { fields } }
 
     pub fn is_out(&self, pos: (usize, usize)) -> bool {
-        let (x, y) = pos;
-
-        // x < 0 || x >= self.fields.len() || y < 0 || y >= self.fields[0].len()
-        x >= self.fields.len() || y >= self.fields[0].len()
+        !self.fields.contains(pos)
     }
 
     pub fn charge(&mut self, pos: (usize, usize)) {
-        if self.is_out(pos) {
-            println!("Is out: {:?}", pos);
-            return;
+        if let Some(octopus) = self.fields.get_mut(pos) {
+            octopus.charge();
         }
+    }
 
-        let (x, y) = pos;
-        self.fields[x][y].charge();
-    }
-
-    fn neighbours(&self, pos: (usize, usize)) -> Vec<(usize, usize)> {
-        let (x, y) = pos;
-
-        // vec![
-        //     (x - 1, y - 1),
-        //     (x - 1, y),
-        //     (x - 1, y + 1),
-        //     (x, y - 1),
-        //     (x, y + 1),
-        //     (x + 1, y - 1),
-        //     (x + 1, y),
-        //     (x + 1, y + 1),
-        // ]
-
-        (-1..=1)
-            .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
-            .filter_map(|(dx, dy)| {
-                let x = x as i32 + dx;
-                let y = y as i32 + dy;
-                if x >= 0
-                    && x < self.fields.len() as i32
-                    && y >= 0
-                    && y < self.fields[0].len() as i32
-                {
-                    Some((x as usize, y as usize))
-                } else {
-                    None
-                }
-            })
-            .filter(|&(x, y)| !(x == pos.0 && y == pos.1))
-            .collect_vec()
+    pub fn neighbours(
+        &self, pos: (usize, usize),
+    ) -> impl Iterator<Item = (usize, usize)> {
+        self.fields.neighbours8(pos)
     }
 
-    pub fn flash(&mut self, pos: (usize, usize)) {
-        if self.is_out(pos) {
-            return;
+    /// Marks the octopus as flashed if it has enough energy and hasn't
+    /// flashed yet in this step. Returns whether it did.
+    fn try_flash(&mut self, pos: (usize, usize)) -> bool {
+        let octopus = &mut self.fields[pos];
+        if octopus.energy <= THRESHOLD || octopus.flashed {
+            return false;
         }
 
-        let (x, y) = pos;
-        let mut octopus = &mut self.fields[x][y];
-        if octopus.energy <= 9 || octopus.flashed {
-            return;
-        }
         octopus.flashed = true;
-
-        for pos in self.neighbours(pos) {
-            self.charge(pos);
-            self.flash(pos);
-        }
+        true
     }
 
-    pub fn print(&self) {
-        println!("\n[Board]");
-        for x in 0..self.fields.len() {
-            for y in 0..self.fields[0].len() {
-                let octopus = &self.fields[x][y];
-                print!("{}", octopus.energy);
-            }
-            println!();
-        }
-    }
+    pub fn print(&self) { print!("\n[Board]\n{}", self.fields); }
 }
 
 fn step(board: &mut Board) -> u32 {
     let mut flashes = 0;
 
-    for octopus in board.fields.iter_mut().flatten() {
+    for octopus in board.fields.iter_mut() {
         octopus.charge();
     }
 
-    for x in 0..board.fields.len() {
-        for y in 0..board.fields[0].len() {
-            board.flash((x, y));
+    // Octopuses that flashed but haven't charged their neighbours yet.
+    //
+    let mut pending = Vec::new();
+    for pos in board.fields.positions() {
+        if board.try_flash(pos) {
+            pending.push(pos);
         }
     }
 
-    for octopus in board.fields.iter_mut().flatten() {
+    while let Some(pos) = pending.pop() {
+        for neighbour in board.neighbours(pos) {
+            board.charge(neighbour);
+            if board.try_flash(neighbour) {
+                pending.push(neighbour);
+            }
+        }
+    }
+
+    for octopus in board.fields.iter_mut() {
         if octopus.flashed {
             octopus.energy = 0;
             flashes += 1;
@@ -132,59 +113,240 @@ fn step(board: &mut Board) -> u32 {
 }
 
 #[aoc_generator(day11)]
-fn parse_input(input: &str) -> Vec<Vec<Octopus>> {
-    input
-        .lines()
-        .map(|line| {
-            line.chars()
-                .filter_map(|c| c.to_digit(10).map(|c| Octopus::new(c as u8)))
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>()
+fn parse_input(input: &str) -> Result<Grid<Octopus>, ParseError> {
+    Ok(Grid::<u8>::parse_digits(input)?.map(|&energy| Octopus::new(energy)))
 }
 
 #[aoc(day11, part1)]
-fn solve_part_1(input: &[Vec<Octopus>]) -> u32 {
-    let mut board = Board::new(input.to_vec());
+fn solve_part_1(input: &Grid<Octopus>) -> u32 {
+    let mut board = Board::new(input.clone());
 
     let mut flashes = 0;
     for _ in 0..100 {
-        // board.print();
-
         flashes += step(&mut board);
     }
 
     flashes
 }
 
-#[aoc(day11, part2)]
-fn solve_part_2(input: &[Vec<Octopus>]) -> u32 {
-    let input = input.to_vec();
+/// The state of the board after a step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub step: u32,
+    pub energies: Grid<u8>,
+    /// The octopuses that flashed during the step.
+    pub flashed: Grid<bool>,
+}
+
+impl Frame {
+    fn new(board: &Board, step: u32) -> Self {
+        // Every octopus that flashed has been reset to 0, while all others
+        // were charged at least once.
+        //
+        Self {
+            step,
+            energies: board.fields.map(|octopus| octopus.energy),
+            flashed: board
+                .fields
+                .map(|octopus| step > 0 && octopus.energy == 0),
+        }
+    }
+
+    pub fn flashes(&self) -> usize {
+        self.flashed.iter().filter(|&&flashed| flashed).count()
+    }
+
+    /// Renders the energies with ANSI colours, see [`Self::cell_colour`].
+    pub fn render_ansi(&self) -> String {
+        let board = self.energies.render_ansi(|pos| {
+            (self.cell_colour(pos), self.energies[pos])
+        });
+
+        format!("[Step {}]\n{}", self.step, board)
+    }
+
+    /// Renders the energies as binary PPM image, where every cell is a
+    /// square of `scale` pixels.
+    pub fn render_ppm(&self, scale: usize) -> Vec<u8> {
+        self.energies.render_ppm(scale, |pos| self.cell_colour(pos))
+    }
+
+    /// Flashed octopuses are highlighted, all others get brighter the more
+    /// energy they have.
+    fn cell_colour(&self, pos: (usize, usize)) -> Rgb {
+        if self.flashed[pos] {
+            return HIGHLIGHT_COLOUR;
+        }
+
+        let energy = self.energies[pos].min(THRESHOLD) as u32;
+        let shade = (energy * 200 / THRESHOLD as u32) as u8;
+        (0, shade / 2, 55 + shade)
+    }
+}
+
+/// Records the initial board and the board after each of the steps.
+pub fn record(input: &Grid<Octopus>, steps: u32) -> Vec<Frame> {
+    let mut board = Board::new(input.clone());
+    let mut frames = vec![Frame::new(&board, 0)];
+
+    for current_step in 1..=steps {
+        step(&mut board);
+        frames.push(Frame::new(&board, current_step));
+    }
+
+    frames
+}
+
+/// Plays the frames back in the terminal, waiting `delay` between them.
+pub fn play(frames: &[Frame], delay: Duration) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+
+    for frame in frames {
+        // Move the cursor to the top left and clear the screen.
+        //
+        write!(stdout, "\x1b[H\x1b[2J{}", frame.render_ansi())?;
+        stdout.flush()?;
+
+        thread::sleep(delay);
+    }
+
+    Ok(())
+}
+
+/// Writes every frame as `step_NNNN.ppm` image into the directory, which is
+/// created if needed.
+pub fn write_frames(
+    dir: impl AsRef<Path>, frames: &[Frame], scale: usize,
+) -> std::io::Result<()> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+
+    for frame in frames {
+        let path = dir.join(format!("step_{:04}.ppm", frame.step));
+        std::fs::write(path, frame.render_ppm(scale))?;
+    }
+
+    Ok(())
+}
+
+/// A sequence of board states that repeats forever: the state after step
+/// `start + length` is the same as after step `start`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: u32,
+    pub length: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct History {
+    /// The first step in which all octopuses flashed.
+    pub first_synchronisation: Option<u32>,
+    pub cycle: Cycle,
+}
+
+/// Steps the board until a state repeats. Since there are only finitely many
+/// states, this always terminates, and a board that didn't synchronise until
+/// then never will.
+pub fn find_cycle(input: &Grid<Octopus>) -> History {
     let mut board = Board::new(input.clone());
+    let mut seen = HashMap::new();
+    let mut first_synchronisation = None;
 
-    let max_flashes = input.len() as u32 * input[0].len() as u32;
+    let max_flashes = input.len() as u32;
+    seen.insert(board.clone(), 0);
     for current_step in 1.. {
         if step(&mut board) == max_flashes {
-            return current_step;
+            first_synchronisation.get_or_insert(current_step);
+        }
+
+        if let Some(start) = seen.insert(board.clone(), current_step) {
+            return History {
+                first_synchronisation,
+                cycle: Cycle {
+                    start,
+                    length: current_step - start,
+                },
+            };
         }
     }
 
     unreachable!()
 }
 
+#[aoc(day11, part2)]
+fn solve_part_2(input: &Grid<Octopus>) -> Option<u32> {
+    find_cycle(input).first_synchronisation
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ParseErrorKind;
+    use itertools::Itertools;
+    use test::Bencher;
+
+    /// The previous implementation, which flashes recursively.
+    fn step_recursive(board: &mut Board) -> u32 {
+        fn flash(board: &mut Board, pos: (usize, usize)) {
+            if !board.try_flash(pos) {
+                return;
+            }
+
+            for pos in board.neighbours(pos).collect_vec() {
+                board.charge(pos);
+                flash(board, pos);
+            }
+        }
+
+        let mut flashes = 0;
+
+        for octopus in board.fields.iter_mut() {
+            octopus.charge();
+        }
+
+        for pos in board.fields.positions() {
+            flash(board, pos);
+        }
+
+        for octopus in board.fields.iter_mut() {
+            if octopus.flashed {
+                octopus.energy = 0;
+                flashes += 1;
+            }
+            octopus.flashed = false;
+        }
+
+        flashes
+    }
+
+    /// A board with pseudo random energies.
+    fn random_board(size: usize) -> Board {
+        let mut state = 0x2545f491u32;
+        let energies = (0..size * size)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                Octopus::new((state % 10) as u8)
+            })
+            .collect();
+
+        Board::new(Grid::from_vec(size, size, energies))
+    }
 
     #[test]
     fn test_neighbours() {
         let input = "11111\n19991\n19191\n19991\n11111";
-        let input = parse_input(input);
+        let input = parse_input(input).unwrap();
         let board = Board::new(input.clone());
 
-        assert_eq!(board.neighbours((0, 0)), [(0, 1), (1, 0), (1, 1)]);
+        assert_eq!(board.neighbours((0, 0)).collect_vec(), [
+            (0, 1),
+            (1, 0),
+            (1, 1)
+        ]);
         assert_eq!(
-            board.neighbours((2, 2)),
+            board.neighbours((2, 2)).collect_vec(),
             [
                 (1, 1),
                 (1, 2),
@@ -201,23 +363,158 @@ mod tests {
     #[test]
     fn test_simple() {
         let input = "11111\n19991\n19191\n19991\n11111";
-        let input = parse_input(input);
+        let input = parse_input(input).unwrap();
 
         assert_eq!(solve_part_1(&input), 259);
-        assert_eq!(solve_part_2(&input), 6);
+        assert_eq!(solve_part_2(&input), Some(6));
     }
 
     fn get_input() -> &'static str {
-        "5483143223\n2745854711\n5264556173\n6141336146\n6357385478\\
-         n4167524645\n2176841721\n6882881134\n4846848554\n5283751526"
+        "5483143223\n2745854711\n5264556173\n6141336146\n6357385478\n\
+         4167524645\n2176841721\n6882881134\n4846848554\n5283751526"
     }
 
     #[test]
     fn test_example() {
         let input = get_input();
-        let input = parse_input(input);
+        let input = parse_input(input).unwrap();
 
         assert_eq!(solve_part_1(&input), 1656);
-        assert_eq!(solve_part_2(&input), 195);
+        assert_eq!(solve_part_2(&input), Some(195));
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let input = parse_input(get_input()).unwrap();
+        let history = find_cycle(&input);
+
+        // Once synchronised, the octopuses flash together every 10 steps.
+        //
+        assert_eq!(history.first_synchronisation, Some(195));
+        assert_eq!(history.cycle, Cycle {
+            start: 195,
+            length: 10
+        });
+    }
+
+    #[test]
+    fn test_never_synchronises() {
+        let input = parse_input("20").unwrap();
+        assert_eq!(find_cycle(&input), History {
+            first_synchronisation: None,
+            cycle: Cycle {
+                start: 0,
+                length: 9
+            }
+        });
+
+        let input = parse_input("5\n1\n0").unwrap();
+        assert_eq!(find_cycle(&input).cycle, Cycle {
+            start: 32,
+            length: 9
+        });
+        assert_eq!(solve_part_2(&input), None);
+    }
+
+    #[test]
+    fn test_record() {
+        let input = parse_input(get_input()).unwrap();
+        let frames = record(&input, 100);
+
+        assert_eq!(frames.len(), 101);
+        assert_eq!(frames[0].energies[(0, 0)], 5);
+        assert_eq!(frames[0].flashes(), 0);
+        assert_eq!(frames[1].flashes(), 0);
+        assert_eq!(frames[2].flashes(), 35);
+        assert_eq!(frames.iter().map(Frame::flashes).sum::<usize>(), 1656);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = parse_input("19\n11").unwrap();
+        let frames = record(&input, 1);
+
+        // The 9 flashes and charges the others to 3.
+        //
+        let shade = (3 * 200 / 9) as u8;
+        let cell = format!(
+            "\x1b[48;2;0;{};{}m\x1b[38;2;0;0;0m3",
+            shade / 2,
+            55 + shade
+        );
+        let flash = "\x1b[48;2;255;230;0m\x1b[38;2;0;0;0m0";
+        assert_eq!(
+            frames[1].render_ansi(),
+            format!(
+                "[Step 1]\n{cell}{flash}\x1b[0m\n{cell}{cell}\x1b[0m\n",
+                cell = cell,
+                flash = flash
+            )
+        );
+
+        let output = frames[1].render_ppm(2);
+        let header = b"P6\n4 4\n255\n";
+        assert!(output.starts_with(header));
+        assert_eq!(output.len(), header.len() + 4 * 4 * 3);
+        assert_eq!(&output[header.len() + 2 * 3..][..3], [255, 230, 0]);
+    }
+
+    #[test]
+    fn test_write_frames() {
+        let input = parse_input("19\n11").unwrap();
+        let frames = record(&input, 1);
+
+        // Use a directory per process, so concurrent runs don't collide, and
+        // remove it before checking the files.
+        //
+        let dir = std::env::temp_dir()
+            .join(format!("aoc_day11_frames_{}", std::process::id()));
+        write_frames(&dir, &frames, 2).unwrap();
+
+        let mut files = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                (path.file_name().unwrap().to_owned(), std::fs::read(path))
+            })
+            .collect::<Vec<_>>();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].0, "step_0000.ppm");
+        assert_eq!(files[1].0, "step_0001.ppm");
+        assert_eq!(files[1].1.as_ref().unwrap(), &frames[1].render_ppm(2));
+    }
+
+    #[test]
+    fn test_matches_recursive() {
+        let mut board = random_board(50);
+        let mut expected = board.clone();
+
+        for _ in 0..200 {
+            assert_eq!(step(&mut board), step_recursive(&mut expected));
+            assert_eq!(board, expected);
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        let error = parse_input("11111\n19x91\n11111").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidDigit);
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(error.text, "x");
+    }
+
+    #[bench]
+    fn bench_step(b: &mut Bencher) {
+        let mut board = random_board(1000);
+        b.iter(|| step(&mut board));
+    }
+
+    #[bench]
+    fn bench_step_recursive(b: &mut Bencher) {
+        let mut board = random_board(1000);
+        b.iter(|| step_recursive(&mut board));
     }
 }
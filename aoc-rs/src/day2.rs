@@ -1,80 +1,693 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::str::FromStr;
+use crate::error::{parse_number, ParseError, ParseErrorKind};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    path::Path,
+};
 
-#[derive(Debug)]
-enum Command {
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
     Forward(u32),
     Down(u32),
     Up(u32),
+    /// The opposite of `Forward`.
+    Back(u32),
+    SetDepth(u32),
+    /// Runs the commands `count` times.
+    Repeat { count: u32, body: Vec<Command> },
 }
 
+fn next_word<'a>(
+    words: &mut impl Iterator<Item = &'a str>, line_index: usize, line: &str,
+) -> Result<&'a str, ParseError> {
+    words.next().ok_or_else(|| {
+        ParseError::new(ParseErrorKind::MissingField, line_index, line, line)
+    })
+}
+
+fn next_number<'a>(
+    words: &mut impl Iterator<Item = &'a str>, line_index: usize, line: &str,
+) -> Result<u32, ParseError> {
+    parse_number(line_index, line, next_word(words, line_index, line)?)
+}
+
+/// How often a command may run through its enclosing `repeat` blocks, so
+/// that a short script can't take billions of steps.
+pub const MAX_REPEAT: u64 = 1_000_000;
+
+/// Parses a script with one command per line. Besides the commands of the
+/// puzzle, scripts can contain `back N`, `set depth N` and blocks like
+/// `repeat K {` ... `}`. Everything after a `#` is a comment. The counts of
+/// nested blocks multiply, and their product can't exceed [`MAX_REPEAT`].
 #[aoc_generator(day2)]
-fn parse_input(input: &str) -> Vec<Command> {
-    input
-        .lines()
-        .filter_map(|line| {
-            // WARNING: When testing things, you must not call `nth` or `next`
-            // since it          consumes the iterator and will thus
-            // result in completely different          outputs.
-            // Either don't print anything, or just use
-            // `collect::<Vec<_>>()`.
-            //
-            //          In this case, nth(1) and nth(0) also caused some
-            // problems for me, so          it's probably best to
-            // just use next() or next_back() instead.
-            //
-            let mut parts = line.split(' ');
-            let num = u32::from_str(parts.next_back().unwrap()).unwrap();
-            Some(match parts.next() {
-                Some("forward") => Command::Forward(num),
-                Some("down") => Command::Down(num),
-                Some("up") => Command::Up(num),
-                _ => {
-                    return None;
+fn parse_input(input: &str) -> Result<Vec<Command>, ParseError> {
+    // The line of each open `repeat`, its count and the commands before it.
+    //
+    let mut blocks = Vec::new();
+    let mut commands = Vec::new();
+
+    for (i, line) in input.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or_default();
+        let mut words = code.split_whitespace();
+        let Some(verb) = words.next() else {
+            continue;
+        };
+
+        // Opening a block doesn't produce a command on its own.
+        //
+        let command = match verb {
+            "forward" => {
+                Some(Command::Forward(next_number(&mut words, i, line)?))
+            }
+            "down" => Some(Command::Down(next_number(&mut words, i, line)?)),
+            "up" => Some(Command::Up(next_number(&mut words, i, line)?)),
+            "back" => Some(Command::Back(next_number(&mut words, i, line)?)),
+            "set" => match next_word(&mut words, i, line)? {
+                "depth" => {
+                    Some(Command::SetDepth(next_number(&mut words, i, line)?))
+                }
+                field => {
+                    let kind = ParseErrorKind::UnknownCommand;
+                    return Err(ParseError::new(kind, i, line, field));
+                }
+            },
+            "repeat" => {
+                let word = next_word(&mut words, i, line)?;
+                let count = parse_number(i, line, word)?;
+                let repeats = blocks
+                    .iter()
+                    .map(|&(_, _, count, _)| u64::from(count))
+                    .product::<u64>();
+                if repeats * u64::from(count) > MAX_REPEAT {
+                    let kind = ParseErrorKind::LimitExceeded {
+                        limit: MAX_REPEAT,
+                    };
+                    return Err(ParseError::new(kind, i, line, word));
                 }
-            })
-        })
-        .collect::<Vec<_>>()
-}
 
-#[aoc(day2, part1)]
-fn solve_part_1(input: &[Command]) -> u32 {
-    let (mut horizontal_position, mut depth) = (0, 0);
-    for command in input {
-        match command {
-            Command::Forward(a) => {
-                horizontal_position += a;
+                let brace = next_word(&mut words, i, line)?;
+                if brace != "{" {
+                    let kind = ParseErrorKind::UnexpectedToken;
+                    return Err(ParseError::new(kind, i, line, brace));
+                }
+
+                blocks.push((i, line, count, std::mem::take(&mut commands)));
+                None
             }
-            Command::Down(a) => {
-                depth += a;
+            "}" => {
+                let Some((_, _, count, outer)) = blocks.pop() else {
+                    let kind = ParseErrorKind::UnexpectedToken;
+                    return Err(ParseError::new(kind, i, line, verb));
+                };
+
+                let body = std::mem::replace(&mut commands, outer);
+                Some(Command::Repeat { count, body })
             }
-            Command::Up(a) => {
-                depth -= a;
+            _ => {
+                return Err(ParseError::new(
+                    ParseErrorKind::UnknownCommand,
+                    i,
+                    line,
+                    verb,
+                ));
             }
+        };
+
+        if let Some(word) = words.next() {
+            let kind = ParseErrorKind::UnexpectedToken;
+            return Err(ParseError::new(kind, i, line, word));
         }
+        commands.extend(command);
+    }
+
+    // Report blocks that are never closed at their `repeat`.
+    //
+    if let Some((i, line, ..)) = blocks.pop() {
+        let kind = ParseErrorKind::UnexpectedEnd;
+        return Err(ParseError::new(kind, i, line, line));
     }
 
-    depth * horizontal_position
+    Ok(commands)
 }
 
-#[aoc(day2, part2)]
-fn solve_part_2(input: &[Command]) -> u32 {
-    let (mut horizontal_position, mut depth, mut aim) = (0, 0, 0);
+/// How `down` and `up` are interpreted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// They change the depth directly (part 1).
+    Depth,
+    /// They change the aim, and moving forward also changes the depth by the
+    /// aim times the distance (part 2).
+    Aim,
+}
 
-    for command in input {
-        match command {
-            Command::Forward(a) => {
-                horizontal_position += a;
-                depth += aim * a;
+/// What happens when the submarine would rise above the surface.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SurfacePolicy {
+    #[default]
+    Error,
+    /// Stop at the surface.
+    Clamp,
+    /// Keep going, which results in a negative depth.
+    AllowNegative,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SubmarineError {
+    Overflow,
+    AboveSurface { depth: i64 },
+}
+
+impl Display for SubmarineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmarineError::Overflow => write!(f, "arithmetic overflow"),
+            SubmarineError::AboveSurface { depth } => {
+                write!(f, "submarine rose above the surface to depth {}", depth)
+            }
+        }
+    }
+}
+
+impl Error for SubmarineError {}
+
+fn add(a: i64, b: i64) -> Result<i64, SubmarineError> {
+    a.checked_add(b).ok_or(SubmarineError::Overflow)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Submarine {
+    pub mode: Mode,
+    pub policy: SurfacePolicy,
+    pub horizontal: i64,
+    pub depth: i64,
+    pub aim: i64,
+}
+
+impl Submarine {
+    /// A submarine at the surface.
+    pub fn new(mode: Mode, policy: SurfacePolicy) -> Self {
+        Self {
+            mode,
+            policy,
+            horizontal: 0,
+            depth: 0,
+            aim: 0,
+        }
+    }
+
+    /// Moves the submarine. If this fails, the submarine isn't moved.
+    pub fn apply(&mut self, command: &Command) -> Result<(), SubmarineError> {
+        let mut next = *self;
+        match (command, self.mode) {
+            (&Command::Forward(a), _) => next.advance(a.into())?,
+            (&Command::Back(a), _) => next.advance(-i64::from(a))?,
+            (&Command::Down(a), Mode::Depth) => {
+                next.depth = add(self.depth, a.into())?;
             }
-            Command::Down(a) => {
-                aim += a;
+            (&Command::Up(a), Mode::Depth) => {
+                next.depth = add(self.depth, -i64::from(a))?;
+            }
+            (&Command::Down(a), Mode::Aim) => {
+                next.aim = add(self.aim, a.into())?;
+            }
+            (&Command::Up(a), Mode::Aim) => {
+                next.aim = add(self.aim, -i64::from(a))?;
+            }
+            (&Command::SetDepth(depth), _) => next.depth = depth.into(),
+            (Command::Repeat { count, body }, _) => {
+                for _ in 0..*count {
+                    for command in body {
+                        next.apply(command)?;
+                    }
+                }
             }
-            Command::Up(a) => {
-                aim -= a;
+        }
+
+        if next.depth < 0 {
+            match self.policy {
+                SurfacePolicy::Error => {
+                    let depth = next.depth;
+                    return Err(SubmarineError::AboveSurface { depth });
+                }
+                SurfacePolicy::Clamp => next.depth = 0,
+                SurfacePolicy::AllowNegative => {}
             }
         }
+
+        *self = next;
+        Ok(())
+    }
+
+    /// Applies all commands in order, stopping at the first error.
+    pub fn run(mut self, commands: &[Command]) -> Result<Self, SubmarineError> {
+        for command in commands {
+            self.apply(command)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Moves forward, or backwards for a negative distance. With aim, this
+    /// also changes the depth.
+    fn advance(&mut self, distance: i64) -> Result<(), SubmarineError> {
+        self.horizontal = add(self.horizontal, distance)?;
+
+        if self.mode == Mode::Aim {
+            let dive = self
+                .aim
+                .checked_mul(distance)
+                .ok_or(SubmarineError::Overflow)?;
+            self.depth = add(self.depth, dive)?;
+        }
+
+        Ok(())
+    }
+
+    fn state(&self, step: usize) -> State {
+        State {
+            step,
+            horizontal: self.horizontal,
+            depth: self.depth,
+            aim: self.aim,
+        }
+    }
+
+    /// The horizontal position multiplied by the depth.
+    pub fn product(&self) -> Result<i64, SubmarineError> {
+        self.horizontal
+            .checked_mul(self.depth)
+            .ok_or(SubmarineError::Overflow)
     }
+}
+
+/// The position of the submarine after `step` commands.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub step: usize,
+    pub horizontal: i64,
+    pub depth: i64,
+    pub aim: i64,
+}
+
+type Commands<'a> = Box<dyn Iterator<Item = &'a Command> + 'a>;
+
+/// The commands in the order they run, with the bodies of `repeat` blocks
+/// repeated.
+fn expand(commands: &[Command]) -> Commands<'_> {
+    Box::new(commands.iter().flat_map(|command| -> Commands<'_> {
+        match command {
+            Command::Repeat { count, body } => {
+                Box::new((0..*count).flat_map(|_| expand(body)))
+            }
+            command => Box::new(std::iter::once(command)),
+        }
+    }))
+}
 
-    depth * horizontal_position
+/// The initial state of the submarine followed by the state after every
+/// command, where the commands in a `repeat` block count once per run. If a
+/// command fails, its error is the last item.
+pub fn trajectory(
+    submarine: Submarine, commands: &[Command],
+) -> impl Iterator<Item = Result<State, SubmarineError>> + '_ {
+    let start = std::iter::once(Ok(submarine.state(0)));
+
+    start.chain(expand(commands).enumerate().scan(
+        Some(submarine),
+        |submarine, (i, command)| {
+            let current = submarine.as_mut()?;
+            Some(match current.apply(command) {
+                Ok(()) => Ok(current.state(i + 1)),
+                Err(error) => {
+                    *submarine = None;
+                    Err(error)
+                }
+            })
+        },
+    ))
+}
+
+pub fn render_csv(states: &[State]) -> String {
+    let mut output = String::from("step,horizontal,depth,aim\n");
+    for state in states {
+        output += &format!(
+            "{},{},{},{}\n",
+            state.step, state.horizontal, state.depth, state.aim
+        );
+    }
+
+    output
+}
+
+/// Renders the course as SVG polyline, with the depth going down. The course
+/// is stretched to fill the whole image.
+pub fn render_svg(states: &[State], width: u32, height: u32) -> String {
+    let (min_x, max_x) = states
+        .iter()
+        .map(|state| state.horizontal)
+        .fold((0, 0), |(min, max), x| (min.min(x), max.max(x)));
+    let (min_y, max_y) = states
+        .iter()
+        .map(|state| state.depth)
+        .fold((0, 0), |(min, max), y| (min.min(y), max.max(y)));
+
+    let points = states
+        .iter()
+        .map(|state| format!("{},{}", state.horizontal, state.depth))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" \
+         height=\"{}\" viewBox=\"{} {} {} {}\" \
+         preserveAspectRatio=\"none\">\n\
+         <polyline points=\"{}\" fill=\"none\" stroke=\"black\" \
+         vector-effect=\"non-scaling-stroke\"/>\n\
+         </svg>\n",
+        width,
+        height,
+        min_x,
+        min_y,
+        (max_x - min_x).max(1),
+        (max_y - min_y).max(1),
+        points
+    )
+}
+
+pub fn write_csv(
+    path: impl AsRef<Path>, states: &[State],
+) -> std::io::Result<()> {
+    std::fs::write(path, render_csv(states))
+}
+
+pub fn write_svg(
+    path: impl AsRef<Path>, states: &[State], width: u32, height: u32,
+) -> std::io::Result<()> {
+    std::fs::write(path, render_svg(states, width, height))
+}
+
+#[aoc(day2, part1)]
+fn solve_part_1(input: &[Command]) -> Result<i64, SubmarineError> {
+    Submarine::new(Mode::Depth, SurfacePolicy::default())
+        .run(input)?
+        .product()
+}
+
+#[aoc(day2, part2)]
+fn solve_part_2(input: &[Command]) -> Result<i64, SubmarineError> {
+    Submarine::new(Mode::Aim, SurfacePolicy::default())
+        .run(input)?
+        .product()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_input() -> &'static str {
+        "forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2"
+    }
+
+    #[test]
+    fn test_example() {
+        let input = parse_input(get_input()).unwrap();
+
+        assert_eq!(solve_part_1(&input), Ok(150));
+        assert_eq!(solve_part_2(&input), Ok(900));
+    }
+
+    #[test]
+    fn test_surface_policy() {
+        let input = parse_input("down 2\nup 5\nforward 3").unwrap();
+        let run = |mode, policy| Submarine::new(mode, policy).run(&input);
+
+        assert_eq!(
+            run(Mode::Depth, SurfacePolicy::Error),
+            Err(SubmarineError::AboveSurface { depth: -3 })
+        );
+        assert_eq!(solve_part_1(&input), Err(SubmarineError::AboveSurface {
+            depth: -3
+        }));
+
+        let submarine = run(Mode::Depth, SurfacePolicy::Clamp).unwrap();
+        assert_eq!((submarine.horizontal, submarine.depth), (3, 0));
+
+        let submarine = run(Mode::Depth, SurfacePolicy::AllowNegative).unwrap();
+        assert_eq!((submarine.horizontal, submarine.depth), (3, -3));
+        assert_eq!(submarine.product(), Ok(-9));
+
+        // With aim, the submarine only rises when moving forward.
+        //
+        let submarine = run(Mode::Aim, SurfacePolicy::AllowNegative).unwrap();
+        assert_eq!((submarine.depth, submarine.aim), (-9, -3));
+        let submarine = run(Mode::Aim, SurfacePolicy::Clamp).unwrap();
+        assert_eq!((submarine.depth, submarine.aim), (0, -3));
+        assert_eq!(
+            run(Mode::Aim, SurfacePolicy::Error),
+            Err(SubmarineError::AboveSurface { depth: -9 })
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let input = parse_input("down 4294967295\nforward 4294967295").unwrap();
+        let mut submarine = Submarine::new(Mode::Aim, SurfacePolicy::Error);
+
+        // The dive of almost 2^64 doesn't fit and the submarine stays put.
+        //
+        submarine.apply(&input[0]).unwrap();
+        let before = submarine;
+        assert_eq!(submarine.apply(&input[1]), Err(SubmarineError::Overflow));
+        assert_eq!(submarine, before);
+
+        let submarine = Submarine {
+            horizontal: i64::MAX,
+            depth: 2,
+            ..Submarine::new(Mode::Depth, SurfacePolicy::Error)
+        };
+        assert_eq!(submarine.product(), Err(SubmarineError::Overflow));
+    }
+
+    #[test]
+    fn test_trajectory() {
+        let input = parse_input(get_input()).unwrap();
+        let states = |mode| {
+            let submarine = Submarine::new(mode, SurfacePolicy::Error);
+            trajectory(submarine, &input)
+                .map(|state| {
+                    let state = state.unwrap();
+                    (state.step, state.horizontal, state.depth, state.aim)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(states(Mode::Depth), [
+            (0, 0, 0, 0),
+            (1, 5, 0, 0),
+            (2, 5, 5, 0),
+            (3, 13, 5, 0),
+            (4, 13, 2, 0),
+            (5, 13, 10, 0),
+            (6, 15, 10, 0)
+        ]);
+        assert_eq!(states(Mode::Aim), [
+            (0, 0, 0, 0),
+            (1, 5, 0, 0),
+            (2, 5, 0, 5),
+            (3, 13, 40, 5),
+            (4, 13, 40, 2),
+            (5, 13, 40, 10),
+            (6, 15, 60, 10)
+        ]);
+
+        // The trajectory ends with the first error.
+        //
+        let input = parse_input("forward 1\nup 1\nforward 1").unwrap();
+        let submarine = Submarine::new(Mode::Depth, SurfacePolicy::Error);
+        let states = trajectory(submarine, &input).collect::<Vec<_>>();
+        assert_eq!(states.len(), 3);
+        assert_eq!(
+            states[2],
+            Err(SubmarineError::AboveSurface { depth: -1 })
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let input = parse_input("forward 2\ndown 3").unwrap();
+        let submarine = Submarine::new(Mode::Depth, SurfacePolicy::Error);
+        let states = trajectory(submarine, &input)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let csv = render_csv(&states);
+        assert_eq!(
+            csv,
+            "step,horizontal,depth,aim\n0,0,0,0\n1,2,0,0\n2,2,3,0\n"
+        );
+
+        let svg = render_svg(&states, 200, 100);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(r#"width="200" height="100" viewBox="0 0 2 3""#));
+        assert!(svg.contains("<polyline points=\"0,0 2,0 2,3\""));
+        assert!(svg.ends_with("</svg>\n"));
+
+        // Repeated commands are drawn one at a time.
+        //
+        let input = parse_input(
+            "repeat 2 {\n\
+             \x20   forward 1\n\
+             \x20   down 1\n\
+             }",
+        )
+        .unwrap();
+        let states = trajectory(submarine, &input)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let svg = render_svg(&states, 200, 100);
+        assert!(svg.contains("<polyline points=\"0,0 1,0 1,1 2,1 2,2\""));
+    }
+
+    #[test]
+    fn test_script() {
+        let input = parse_input(
+            "# Dive, then come back up\n\
+             down 10\n\
+             repeat 2 {\n\
+             \x20   forward 3 # with aim\n\
+             \x20   repeat 3 {\n\
+             \x20       up 1\n\
+             \x20   }\n\
+             }\n\
+             \n\
+             back 4\n\
+             set depth 7",
+        )
+        .unwrap();
+
+        assert_eq!(input, [
+            Command::Down(10),
+            Command::Repeat {
+                count: 2,
+                body: vec![Command::Forward(3), Command::Repeat {
+                    count: 3,
+                    body: vec![Command::Up(1)]
+                }]
+            },
+            Command::Back(4),
+            Command::SetDepth(7)
+        ]);
+
+        // Forward 3 with aim 10 and 7, then back 4 with aim 4.
+        //
+        let submarine = Submarine::new(Mode::Aim, SurfacePolicy::Error);
+        let states = trajectory(submarine, &input[..3])
+            .map(|state| {
+                let state = state.unwrap();
+                (state.step, state.horizontal, state.depth, state.aim)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(states, [
+            (0, 0, 0, 0),
+            (1, 0, 0, 10),
+            (2, 3, 30, 10),
+            (3, 3, 30, 9),
+            (4, 3, 30, 8),
+            (5, 3, 30, 7),
+            (6, 6, 51, 7),
+            (7, 6, 51, 6),
+            (8, 6, 51, 5),
+            (9, 6, 51, 4),
+            (10, 2, 35, 4)
+        ]);
+
+        let submarine = submarine.run(&input).unwrap();
+        assert_eq!((submarine.horizontal, submarine.depth), (2, 7));
+
+        let submarine = Submarine::new(Mode::Depth, SurfacePolicy::Error);
+        let submarine = submarine.run(&input[..3]).unwrap();
+        assert_eq!((submarine.horizontal, submarine.depth), (2, 4));
+    }
+
+    #[test]
+    fn test_script_malformed() {
+        let error = parse_input("repeat 2 {\n  forward 1\n  jump 3\n}")
+            .unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnknownCommand);
+        assert_eq!((error.line, error.column), (3, 3));
+        assert_eq!(error.text, "jump");
+
+        let error = parse_input("set aim 3").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnknownCommand);
+        assert_eq!((error.line, error.column), (1, 5));
+
+        let error = parse_input("forward 1 2").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedToken);
+        assert_eq!((error.line, error.column), (1, 11));
+
+        let error = parse_input("repeat 2 (").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedToken);
+        assert_eq!(error.text, "(");
+
+        let error = parse_input("forward 1\n}").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedToken);
+        assert_eq!(error.line, 2);
+
+        let error =
+            parse_input("repeat 2 {\nrepeat 3 {\nup 1\n}").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(error.line, 1);
+    }
+
+    #[test]
+    fn test_script_repeat_limit() {
+        let limit = ParseErrorKind::LimitExceeded { limit: MAX_REPEAT };
+
+        let error = parse_input(
+            "repeat 4294967295 {\n\
+             \x20   forward 1\n\
+             }",
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, limit);
+        assert_eq!((error.line, error.column), (1, 8));
+        assert_eq!(
+            error.to_string(),
+            "line 1, column 8: exceeds the limit of 1000000 `4294967295`"
+        );
+
+        let script = "repeat 1000 {\n\
+                      \x20   repeat 1001 {\n\
+                      \x20       forward 1\n\
+                      \x20   }\n\
+                      }";
+        let error = parse_input(script).unwrap_err();
+        assert_eq!(error.kind, limit);
+        assert_eq!((error.line, error.column), (2, 12));
+        assert_eq!(error.text, "1001");
+
+        let script = "repeat 1000 {\n\
+                      \x20   repeat 1000 {\n\
+                      \x20       forward 1\n\
+                      \x20   }\n\
+                      }";
+        assert!(parse_input(script).is_ok());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let error = parse_input("forward 5\nsideways 3").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnknownCommand);
+        assert_eq!((error.line, error.column), (2, 1));
+        assert_eq!(error.text, "sideways");
+
+        let error = parse_input("forward 5\ndown x").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidNumber);
+        assert_eq!((error.line, error.column), (2, 6));
+
+        let error = parse_input("forward 5\ndown").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::MissingField);
+        assert_eq!(error.line, 2);
+    }
 }
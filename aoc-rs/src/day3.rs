@@ -1,4 +1,59 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use crate::error::{ParseError, ParseErrorKind};
+
+/// The diagnostic report, where every number is stored in the lowest `width`
+/// bits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub width: usize,
+    pub numbers: Vec<u64>,
+}
+
+impl Report {
+    /// Bit `i` of the number, counting from the most significant one.
+    pub fn bit(&self, number: u64, i: usize) -> u64 {
+        number >> (self.width - i - 1) & 1
+    }
+
+    /// A number with all `width` bits set.
+    pub fn mask(&self) -> u64 {
+        u64::MAX.checked_shr(64 - self.width as u32).unwrap_or(0)
+    }
+
+    /// The number of ones in each column, starting with the most significant
+    /// bit.
+    ///
+    /// All columns are counted at once with bit-sliced counters, where bit `c`
+    /// of `planes[k]` is bit `k` of the count of column `c`. Adding a number is
+    /// a ripple carry through the planes, which on average stops after two.
+    pub fn one_counts(&self) -> Vec<usize> {
+        let mut planes = [0u64; usize::BITS as usize];
+
+        for &number in &self.numbers {
+            let mut carry = number;
+            for plane in planes.iter_mut() {
+                if carry == 0 {
+                    break;
+                }
+
+                let overflow = *plane & carry;
+                *plane ^= carry;
+                carry = overflow;
+            }
+        }
+
+        (0..self.width)
+            .rev()
+            .map(|column| {
+                planes
+                    .iter()
+                    .enumerate()
+                    .map(|(k, plane)| ((plane >> column & 1) as usize) << k)
+                    .sum()
+            })
+            .collect()
+    }
+}
 
 fn binary_to_int(number: &[u8]) -> u64 {
     let mut result = 0u64;
@@ -10,108 +65,209 @@ fn binary_to_int(number: &[u8]) -> u64 {
     result
 }
 
-fn find_rating<F: Fn(usize, usize) -> u8, const T: usize>(
-    input: &[[u8; T]], bit_criteria: F,
-) -> [u8; T] {
-    let mut filtered_bits = input.to_vec();
-    for i in 0..T {
-        let one_count = filtered_bits.iter().filter(|bit| bit[i] == 1).count();
-        let zero_count = filtered_bits.iter().filter(|bit| bit[i] == 0).count();
-
-        let bit_criteria = bit_criteria(one_count, zero_count);
-        filtered_bits.retain(|bit| bit[i] == bit_criteria);
+/// Finds the rating in the numbers of the report, which need to be sorted.
+///
+/// The remaining candidates always share their leading bits, so they form a
+/// range of the sorted numbers in which the ones with a zero in the current
+/// column come first. That way each column only needs a binary search.
+fn find_rating<F: Fn(usize, usize) -> u64>(
+    sorted: &Report, bit_criteria: F,
+) -> u64 {
+    let numbers = &sorted.numbers;
+    debug_assert!(numbers.windows(2).all(|pair| pair[0] <= pair[1]));
 
-        if filtered_bits.len() == 1 {
+    let (mut start, mut end) = (0, numbers.len());
+    for i in 0..sorted.width {
+        if end - start <= 1 {
             break;
         }
+
+        let split = start
+            + numbers[start..end]
+                .partition_point(|&number| sorted.bit(number, i) == 0);
+        let one_count = end - split;
+        let zero_count = split - start;
+
+        if bit_criteria(one_count, zero_count) == 1 {
+            start = split;
+        } else {
+            end = split;
+        }
     }
 
-    assert_eq!(filtered_bits.len(), 1);
+    assert_eq!(end - start, 1);
 
-    filtered_bits[0]
+    numbers[start]
 }
 
+/// Parses the report. The width is taken from the first line, and all other
+/// lines need to have the same width.
 #[aoc_generator(day3)]
-fn parse_input(input: &str) -> Vec<[u8; 12]> {
-    input
+fn parse_input(input: &str) -> Result<Report, ParseError> {
+    let Some(first) = input.lines().next() else {
+        let kind = ParseErrorKind::UnexpectedEnd;
+        return Err(ParseError::new(kind, 0, input, input));
+    };
+    let width = first.len();
+
+    let numbers = input
         .lines()
-        .map(|line| {
-            let mut bytes: [u8; 12] = [0u8; 12];
-            for (i, char) in line.chars().enumerate() {
-                bytes[i] += char.to_digit(10).unwrap() as u8;
+        .enumerate()
+        .map(|(i, line)| {
+            // Only 64 bits fit into a number.
+            //
+            let expected = width.min(64);
+            if line.len() != expected {
+                return Err(ParseError::new(
+                    ParseErrorKind::UnexpectedLength {
+                        expected,
+                        found: line.len(),
+                    },
+                    i,
+                    line,
+                    line,
+                ));
             }
-            bytes
+
+            let mut bits = Vec::with_capacity(width);
+            for (j, char) in line.char_indices() {
+                bits.push(match char {
+                    '0' => 0,
+                    '1' => 1,
+                    _ => {
+                        return Err(ParseError::new(
+                            ParseErrorKind::InvalidDigit,
+                            i,
+                            line,
+                            &line[j..j + char.len_utf8()],
+                        ));
+                    }
+                });
+            }
+
+            Ok(binary_to_int(&bits))
         })
-        .collect::<Vec<_>>()
+        .collect::<Result<_, _>>()?;
+
+    Ok(Report { width, numbers })
 }
 
+/// Returns `None` if the product doesn't fit, which can happen for reports
+/// that are more than 32 bits wide.
 #[aoc(day3, part1)]
-fn solve_part_1<const T: usize>(input: &[[u8; T]]) -> u64 {
-    let mut gamma_rate: [u8; T] = [0u8; T];
+fn solve_part_1(input: &Report) -> Option<u64> {
+    let mut gamma_rate = 0u64;
 
-    for i in 0..T {
-        let zero_bits = input.iter().filter(|x| x[i] == 0).count();
-        let one_bits = input.iter().filter(|x| x[i] == 1).count();
+    for one_bits in input.one_counts() {
+        let zero_bits = input.numbers.len() - one_bits;
 
+        gamma_rate <<= 1;
         if one_bits > zero_bits {
-            gamma_rate[i] = 1;
-        } else {
-            gamma_rate[i] = 0;
+            gamma_rate |= 1;
         }
     }
 
     // Just flip the value
     //
-    let epsilon_rate = gamma_rate
-        .iter()
-        .map(|bit| if *bit == 1 { 0 } else { 1 })
-        .collect::<Vec<_>>();
-
-    // Convert to int
-    //
-    let gamma_int = binary_to_int(&gamma_rate);
-    let epsilon_int = binary_to_int(&epsilon_rate);
+    let epsilon_rate = !gamma_rate & input.mask();
 
-    gamma_int * epsilon_int
+    gamma_rate.checked_mul(epsilon_rate)
 }
 
+/// Returns `None` if the product doesn't fit, see [`solve_part_1`].
 #[aoc(day3, part2)]
-fn solve_part_2<const T: usize>(input: &[[u8; T]]) -> u64 {
+fn solve_part_2(input: &Report) -> Option<u64> {
+    let mut sorted = input.clone();
+    sorted.numbers.sort_unstable();
+
     let oxygen_generator_rating = find_rating(
-        input,
+        &sorted,
         |one_count, zero_count| if one_count >= zero_count { 1 } else { 0 },
     );
     let co2_scrubber_rating =
         find_rating(
-            input,
+            &sorted,
             |one_count, zero_count| if zero_count <= one_count { 0 } else { 1 },
         );
 
-    let oxygen_generator_int = binary_to_int(&oxygen_generator_rating);
-    let co2_scrubber_int = binary_to_int(&co2_scrubber_rating);
-
-    oxygen_generator_int * co2_scrubber_int
+    oxygen_generator_rating.checked_mul(co2_scrubber_rating)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use test::Bencher;
 
-    fn get_input() -> [[u8; 5]; 12] {
-        [
-            [0, 0, 1, 0, 0],
-            [1, 1, 1, 1, 0],
-            [1, 0, 1, 1, 0],
-            [1, 0, 1, 1, 1],
-            [1, 0, 1, 0, 1],
-            [0, 1, 1, 1, 1],
-            [0, 0, 1, 1, 1],
-            [1, 1, 1, 0, 0],
-            [1, 0, 0, 0, 0],
-            [1, 1, 0, 0, 1],
-            [0, 0, 0, 1, 0],
-            [0, 1, 0, 1, 0],
-        ]
+    /// The previous implementation, which counts the bits of every column
+    /// separately.
+    fn solve_part_1_filter(input: &Report) -> Option<u64> {
+        let mut gamma_rate = 0u64;
+
+        for i in 0..input.width {
+            let one_bits = input
+                .numbers
+                .iter()
+                .filter(|&&number| input.bit(number, i) == 1)
+                .count();
+            let zero_bits = input.numbers.len() - one_bits;
+
+            gamma_rate <<= 1;
+            if one_bits > zero_bits {
+                gamma_rate |= 1;
+            }
+        }
+
+        gamma_rate.checked_mul(!gamma_rate & input.mask())
+    }
+
+    /// The previous implementation, which filters the candidates one column
+    /// at a time.
+    fn find_rating_filter<F: Fn(usize, usize) -> u64>(
+        report: &Report, bit_criteria: F,
+    ) -> u64 {
+        let mut filtered_bits = report.numbers.clone();
+        for i in 0..report.width {
+            let one_count = filtered_bits
+                .iter()
+                .filter(|&&number| report.bit(number, i) == 1)
+                .count();
+            let zero_count = filtered_bits.len() - one_count;
+
+            let bit_criteria = bit_criteria(one_count, zero_count);
+            filtered_bits
+                .retain(|&number| report.bit(number, i) == bit_criteria);
+
+            if filtered_bits.len() == 1 {
+                break;
+            }
+        }
+
+        assert_eq!(filtered_bits.len(), 1);
+
+        filtered_bits[0]
+    }
+
+    fn solve_part_2_filter(input: &Report) -> Option<u64> {
+        let oxygen_generator_rating = find_rating_filter(input, |one, zero| {
+            if one >= zero { 1 } else { 0 }
+        });
+        let co2_scrubber_rating = find_rating_filter(input, |one, zero| {
+            if zero <= one { 0 } else { 1 }
+        });
+
+        oxygen_generator_rating.checked_mul(co2_scrubber_rating)
+    }
+
+    fn get_sorted_input() -> Report {
+        let mut input = parse_input(get_input()).unwrap();
+        input.numbers.sort_unstable();
+        input
+    }
+
+    fn get_input() -> &'static str {
+        "00100\n11110\n10110\n10111\n10101\n01111\n00111\n11100\n10000\n\
+         11001\n00010\n01010"
     }
 
     #[test]
@@ -122,16 +278,36 @@ mod tests {
         assert_eq!(binary_to_int(&[1, 1, 1, 0, 1, 1, 1, 1, 0, 1, 0, 1]), 3829);
     }
 
+    #[test]
+    fn test_parse_input() {
+        let input = parse_input(get_input()).unwrap();
+
+        assert_eq!(input.width, 5);
+        assert_eq!(input.numbers.len(), 12);
+        assert_eq!(input.numbers[1], 0b11110);
+        assert_eq!(input.mask(), 0b11111);
+        assert_eq!(input.bit(0b10000, 0), 1);
+        assert_eq!(input.bit(0b10000, 4), 0);
+        assert_eq!(input.one_counts(), [7, 5, 8, 7, 5]);
+    }
+
+    #[test]
+    fn test_solve_part_1() {
+        let input = parse_input(get_input()).unwrap();
+        let result = solve_part_1(&input);
+        assert_eq!(result, Some(198));
+    }
+
     #[test]
     fn test_solve_part_2() {
-        let input = get_input();
+        let input = parse_input(get_input()).unwrap();
         let result = solve_part_2(&input);
-        assert_eq!(result, 230);
+        assert_eq!(result, Some(230));
     }
 
     #[test]
     fn test_find_oxygen_generator_rating() {
-        let input = get_input();
+        let input = get_sorted_input();
 
         let result =
             find_rating(
@@ -144,12 +320,12 @@ mod tests {
                     }
                 },
             );
-        assert_eq!(result, [1, 0, 1, 1, 1]);
+        assert_eq!(result, 0b10111);
     }
 
     #[test]
     fn test_find_co2_scrubber_rating() {
-        let input = get_input();
+        let input = get_sorted_input();
 
         let result =
             find_rating(
@@ -162,6 +338,100 @@ mod tests {
                     }
                 },
             );
-        assert_eq!(result, [0, 1, 0, 1, 0]);
+        assert_eq!(result, 0b01010);
+    }
+
+    #[test]
+    fn test_real() {
+        let input =
+            parse_input(include_str!("../input/2021/day3.txt")).unwrap();
+
+        assert_eq!(input.width, 12);
+        assert_eq!(solve_part_1(&input), Some(2035764));
+        assert_eq!(solve_part_2(&input), Some(2817661));
+        assert_eq!(solve_part_1_filter(&input), Some(2035764));
+        assert_eq!(solve_part_2_filter(&input), Some(2817661));
+    }
+
+    #[test]
+    fn test_wide_report() {
+        // Only the first column has more ones, so gamma is 2^39 and epsilon
+        // is 2^39 - 1.
+        //
+        let (high, low) = (format!("1{}", "0".repeat(39)), "1".repeat(39));
+        let input =
+            parse_input(&format!("{0}\n{0}\n0{1}", high, low)).unwrap();
+        assert_eq!(input.width, 40);
+        assert_eq!(solve_part_1(&input), None);
+        assert_eq!(solve_part_1_filter(&input), None);
+
+        // The ratings are 2^39 and 2^39 - 1 as well.
+        //
+        let input = parse_input(&format!("{}\n0{}", high, low)).unwrap();
+        assert_eq!(solve_part_1(&input), Some(0));
+        assert_eq!(solve_part_2(&input), None);
+        assert_eq!(solve_part_2_filter(&input), None);
+    }
+
+    proptest! {
+        #[test]
+        fn test_matches_filter(
+            width in 1usize..=16,
+            numbers in prop::collection::btree_set(0u64..1 << 16, 1..100),
+        ) {
+            let numbers = numbers.into_iter().map(|n| n >> (16 - width));
+            let mut input = Report {
+                width,
+                numbers: numbers.collect(),
+            };
+            input.numbers.dedup();
+            prop_assert_eq!(solve_part_1(&input), solve_part_1_filter(&input));
+
+            // The CO2 scrubber rating doesn't exist if a column only has
+            // ones, so only compare the oxygen generator rating.
+            //
+            let criteria = |one, zero| if one >= zero { 1 } else { 0 };
+            let expected = find_rating_filter(&input, criteria);
+            input.numbers.sort_unstable();
+            prop_assert_eq!(find_rating(&input, criteria), expected);
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        let error = parse_input("110001010110\n1100010").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedLength {
+            expected: 12,
+            found: 7
+        });
+        assert_eq!(error.line, 2);
+
+        let error = parse_input("110001010110\n110001210110").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidDigit);
+        assert_eq!((error.line, error.column), (2, 7));
+        assert_eq!(error.text, "2");
+
+        let error = parse_input(&"1".repeat(65)).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedLength {
+            expected: 64,
+            found: 65
+        });
+
+        let error = parse_input("").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedEnd);
+    }
+
+    #[bench]
+    fn bench_packed(b: &mut Bencher) {
+        let input =
+            parse_input(include_str!("../input/2021/day3.txt")).unwrap();
+        b.iter(|| (solve_part_1(&input), solve_part_2(&input)));
+    }
+
+    #[bench]
+    fn bench_filter(b: &mut Bencher) {
+        let input =
+            parse_input(include_str!("../input/2021/day3.txt")).unwrap();
+        b.iter(|| (solve_part_1_filter(&input), solve_part_2_filter(&input)));
     }
 }
@@ -1,80 +1,109 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Itertools;
-use std::{
-    fmt::{Display, Formatter},
-    str::FromStr,
+use crate::{
+    error::{parse_number, ParseError, ParseErrorKind},
+    grid::Grid,
 };
+use itertools::Itertools;
+use std::fmt::{Display, Formatter};
 
-#[derive(Copy, Clone, Default)]
-pub struct BingoBoard([[u8; 5]; 5]);
-
-/// The value that will be used to mark cells of numbers on the bingo board that
-/// have been drawn.
-pub const MARKER: u8 = u8::MAX;
+/// A bingo board of any size, which is stored row by row. The cells of numbers
+/// that have been drawn are `None`.
+#[derive(Clone, Debug)]
+pub struct BingoBoard(Grid<Option<u32>>);
 
 impl BingoBoard {
-    pub fn new(lines: &[&str]) -> Self {
-        let mut board = [[0; 5]; 5];
+    /// Parses the board from its rows, which all need to have the same number
+    /// of cells. The line numbers of the returned error are relative to the
+    /// first row of the board.
+    pub fn new(lines: &[&str]) -> Result<Self, ParseError> {
+        let Some(first) = lines.first() else {
+            let kind = ParseErrorKind::UnexpectedEnd;
+            return Err(ParseError::new(kind, 0, "", ""));
+        };
+
+        let width = first.split_whitespace().count();
+        let mut cells = Vec::with_capacity(width * lines.len());
 
-        assert_eq!(lines.len(), 5);
         for (i, line) in lines.iter().enumerate() {
-            for (j, c) in line.split_whitespace().enumerate() {
-                board[i][j] = u8::from_str(c).unwrap();
+            let row = line.split_whitespace().collect::<Vec<_>>();
+            if row.len() != width {
+                return Err(ParseError::new(
+                    ParseErrorKind::UnexpectedLength {
+                        expected: width,
+                        found: row.len(),
+                    },
+                    i,
+                    line,
+                    line,
+                ));
+            }
+
+            for c in row {
+                cells.push(Some(parse_number(i, line, c)?));
             }
         }
 
-        BingoBoard(board)
+        Ok(BingoBoard(Grid::from_vec(width, lines.len(), cells)))
     }
 
-    pub fn get_column(&self, i: usize) -> Vec<u8> {
-        self.0.iter().map(|row| row[i]).collect()
+    pub fn width(&self) -> usize { self.0.width() }
+
+    pub fn height(&self) -> usize { self.0.height() }
+
+    pub fn get_column(&self, i: usize) -> Vec<Option<u32>> {
+        self.0.column(i).copied().collect()
     }
 
     /// Marks the cell that contains the specified number. If the number is not
     /// defined, nothing will happen.
-    pub fn assign_number(&mut self, number: u8) {
-        if let Some((position, _)) =
-            self.0.iter().flatten().find_position(|&&n| n == number)
-        {
-            let row = position / 5;
-            let col = position % 5;
-
-            self.0[row][col] = MARKER;
+    pub fn assign_number(&mut self, number: u32) {
+        if let Some(cell) = self.0.iter_mut().find(|n| **n == Some(number)) {
+            *cell = None;
         }
     }
 
+    /// The cells that have been marked.
+    pub fn marks(&self) -> Mask {
+        let marked = self.0.positions().filter(|&pos| self.0[pos].is_none());
+        Mask::from_cells(self.width(), self.height(), marked)
+    }
+
+    /// Whether a complete row or column has been marked. This is the same as
+    /// [`WinPattern::RowsAndColumns`], but doesn't need to build the masks.
     pub fn has_won(&self) -> bool {
-        let column =
-            (0..5).any(|i| self.get_column(i).iter().all(|&n| n == MARKER));
-        let row = self.0.iter().any(|row| row.iter().all(|&c| c == MARKER));
+        let column = (0..self.width())
+            .any(|i| self.0.column(i).all(Option::is_none));
+        let row = self.0.rows().any(|row| row.iter().all(Option::is_none));
 
         column || row
     }
 
-    /// Sum of all cells that have not been marked.
-    pub fn unmarked_number_sum(&self) -> usize {
-        self.0
+    pub fn has_won_with(&self, pattern: &WinPattern) -> bool {
+        let marks = self.marks();
+
+        pattern
+            .masks(self.width(), self.height())
             .iter()
-            .flatten()
-            .map(|&n| n as usize)
-            .filter(|&n| n != MARKER.into())
-            .sum()
+            .any(|mask| mask.is_subset(&marks))
+    }
+
+    /// Sum of all cells that have not been marked.
+    pub fn unmarked_number_sum(&self) -> u64 {
+        self.0.iter().flatten().map(|&n| u64::from(n)).sum()
     }
 
     /// The maximum number of cells that have already been marked (either per
     /// row or column)
     pub fn max_cells_marked(&self) -> usize {
-        let col_marks = (0..5)
-            .map(|i| {
-                self.get_column(i).iter().filter(|&&n| n == MARKER).count()
-            })
+        let col_marks = (0..self.width())
+            .map(|i| self.0.column(i).filter(|n| n.is_none()).count())
             .max()
             .unwrap_or_default();
 
         let row_marks = self
             .0
-            .iter()
-            .map(|row| row.iter().filter(|&&c| c == MARKER).count())
+            .rows()
+            .map(|row| row.iter().filter(|c| c.is_none()).count())
             .max()
             .unwrap_or_default();
 
@@ -88,99 +117,286 @@ impl BingoBoard {
 
 impl Display for BingoBoard {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for row in self.0 {
-            writeln!(f, "{:?}", row)?;
+        // Marked cells are shown as `x`.
+        //
+        for row in self.0.rows() {
+            let cells = row.iter().map(|cell| match cell {
+                Some(n) => n.to_string(),
+                None => "x".to_string(),
+            });
+            writeln!(f, "[{}]", cells.format(", "))?;
         }
 
         Ok(())
     }
 }
 
-#[aoc_generator(day4)]
-fn parse_input(input: &str) -> (Vec<u8>, Vec<BingoBoard>) {
-    let drawn_numbers = input
-        .lines()
-        .next()
-        .map(|line| line.split(',').map(|s| u8::from_str(s).unwrap()).collect())
-        .unwrap();
-
-    let boards = input
-        .lines()
-        .skip(1)
-        .filter(|line| !line.is_empty())
-        .collect::<Vec<_>>()
-        .chunks(5)
-        .map(BingoBoard::new)
-        .collect::<Vec<_>>();
+/// A set of cells of a board, where bit `row * width + column` is set for
+/// every cell in the set.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    words: Vec<u64>,
+}
+
+impl Mask {
+    /// An empty mask for a board of the given size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Mask {
+            width,
+            height,
+            words: vec![0; (width * height).div_ceil(64)],
+        }
+    }
+
+    /// Panics if a cell is outside the board.
+    pub fn from_cells(
+        width: usize, height: usize,
+        cells: impl IntoIterator<Item = (usize, usize)>,
+    ) -> Self {
+        let mut mask = Mask::new(width, height);
+        for (row, col) in cells {
+            assert!(row < height && col < width, "cell is out of bounds");
+            mask.insert(row * width + col);
+        }
+
+        mask
+    }
+
+    pub fn width(&self) -> usize { self.width }
+
+    pub fn height(&self) -> usize { self.height }
 
-    (drawn_numbers, boards)
+    pub fn is_empty(&self) -> bool { self.words.iter().all(|&word| word == 0) }
+
+    pub fn insert(&mut self, index: usize) {
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|word| word & 1 << (index % 64) != 0)
+    }
+
+    /// Whether all cells of this mask are also in the other mask.
+    ///
+    /// Panics if the masks are for boards of different sizes.
+    pub fn is_subset(&self, other: &Mask) -> bool {
+        assert_eq!(
+            (self.width, self.height),
+            (other.width, other.height),
+            "masks are for boards of different sizes"
+        );
+
+        self.words.iter().zip(&other.words).all(|(a, b)| a & !b == 0)
+    }
 }
 
-fn find_winner(
-    drawn_numbers: &[u8], boards: &mut [BingoBoard],
-) -> Option<(u8, BingoBoard)> {
-    for number in drawn_numbers {
-        for board in boards.iter_mut() {
-            board.assign_number(*number);
+/// The cells that need to be marked for a board to win.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum WinPattern {
+    /// Any complete row or column, as in the puzzle.
+    #[default]
+    RowsAndColumns,
+    /// Either of the two diagonals. Boards that aren't square can't win.
+    Diagonals,
+    FourCorners,
+    /// Every cell of the board.
+    Blackout,
+    /// Any of the masks, which need to match the size of the board and can't
+    /// be empty.
+    Custom(Vec<Mask>),
+    /// Any of the patterns, e.g. rows, columns and diagonals.
+    Any(Vec<WinPattern>),
+}
 
-            if board.has_won() {
-                return Some((*number, *board));
+impl WinPattern {
+    /// The masks of a board with the given size, of which at least one needs
+    /// to be marked completely.
+    ///
+    /// Panics if a custom mask is empty, which would win before any number
+    /// is drawn, or if its size doesn't match.
+    pub fn masks(&self, width: usize, height: usize) -> Vec<Mask> {
+        let mask = |cells: Vec<(usize, usize)>| {
+            Mask::from_cells(width, height, cells)
+        };
+
+        match self {
+            WinPattern::RowsAndColumns => {
+                let rows = (0..height).map(|row| {
+                    mask((0..width).map(|col| (row, col)).collect())
+                });
+                let columns = (0..width).map(|col| {
+                    mask((0..height).map(|row| (row, col)).collect())
+                });
+
+                rows.chain(columns).collect()
+            }
+            WinPattern::Diagonals if width == height && width > 0 => vec![
+                mask((0..width).map(|i| (i, i)).collect()),
+                mask((0..width).map(|i| (i, width - 1 - i)).collect()),
+            ],
+            WinPattern::Diagonals => Vec::new(),
+            WinPattern::FourCorners if width > 0 && height > 0 => {
+                vec![mask(vec![
+                    (0, 0),
+                    (0, width - 1),
+                    (height - 1, 0),
+                    (height - 1, width - 1),
+                ])]
+            }
+            WinPattern::FourCorners => Vec::new(),
+            WinPattern::Blackout => vec![mask(
+                (0..height).cartesian_product(0..width).collect(),
+            )],
+            WinPattern::Custom(masks) => {
+                for mask in masks {
+                    assert_eq!(
+                        (mask.width, mask.height),
+                        (width, height),
+                        "mask is for a board of a different size"
+                    );
+                    assert!(!mask.is_empty(), "mask is empty");
+                }
+
+                masks.clone()
             }
+            WinPattern::Any(patterns) => patterns
+                .iter()
+                .flat_map(|pattern| pattern.masks(width, height))
+                .collect(),
         }
     }
+}
+
+#[aoc_generator(day4)]
+fn parse_input(
+    input: &str,
+) -> Result<(Vec<u32>, Vec<BingoBoard>), ParseError> {
+    let mut lines = input.lines().enumerate();
+
+    let drawn_numbers = match lines.next() {
+        Some((i, line)) => line
+            .split(',')
+            .map(|s| parse_number(i, line, s))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => {
+            return Err(ParseError::new(
+                ParseErrorKind::UnexpectedEnd,
+                0,
+                input,
+                input,
+            ));
+        }
+    };
+
+    // Every board is a block of lines, separated by empty lines.
+    //
+    let boards = lines
+        .collect::<Vec<_>>()
+        .split(|(_, line)| line.trim().is_empty())
+        .filter(|chunk| !chunk.is_empty())
+        .map(|chunk| {
+            let lines = chunk.iter().map(|&(_, line)| line).collect_vec();
+
+            // Translate the line number back into the line of the input.
+            //
+            BingoBoard::new(&lines).map_err(|error| ParseError {
+                line: chunk[error.line - 1].0 + 1,
+                ..error
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    None
+    Ok((drawn_numbers, boards))
 }
 
-fn find_last_board(
-    drawn_numbers: Vec<u8>, mut boards: Vec<BingoBoard>,
-) -> Option<(u8, BingoBoard)> {
-    for number in drawn_numbers {
-        // If we found the last board, return it.
-        //
-        if let [mut board] = *boards {
+/// A board completing a row or column.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Win {
+    /// The index of the number that completed the board.
+    pub draw_index: usize,
+    pub number: u32,
+    pub board_index: usize,
+    /// The number multiplied with the sum of all unmarked cells.
+    pub score: u128,
+}
+
+/// All wins of a game in the order in which they happened. Boards that win
+/// with the same number are ordered by their index.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Timeline {
+    pub wins: Vec<Win>,
+}
+
+impl Timeline {
+    pub fn first(&self) -> Option<&Win> { self.wins.first() }
+
+    pub fn last(&self) -> Option<&Win> { self.wins.last() }
+
+    /// The `k`-th board to win, starting at 0.
+    pub fn winner(&self, k: usize) -> Option<&Win> { self.wins.get(k) }
+}
+
+/// Draws the numbers until all boards have won or there are no numbers left.
+pub fn play(drawn_numbers: &[u32], boards: &[BingoBoard]) -> Timeline {
+    play_with(drawn_numbers, boards, &WinPattern::default())
+}
+
+pub fn play_with(
+    drawn_numbers: &[u32], boards: &[BingoBoard], pattern: &WinPattern,
+) -> Timeline {
+    let masks = boards
+        .iter()
+        .map(|board| pattern.masks(board.width(), board.height()))
+        .collect::<Vec<_>>();
+
+    let mut boards = boards.to_vec();
+    let mut remaining = (0..boards.len()).collect::<Vec<_>>();
+    let mut wins = Vec::new();
+
+    for (draw_index, &number) in drawn_numbers.iter().enumerate() {
+        if remaining.is_empty() {
+            break;
+        }
+
+        remaining.retain(|&board_index| {
+            let board = &mut boards[board_index];
             board.assign_number(number);
 
-            if board.has_won() {
-                return Some((number, board));
-            } else {
-                continue;
+            let marks = board.marks();
+            if !masks[board_index].iter().any(|mask| mask.is_subset(&marks)) {
+                return true;
             }
-        }
 
-        // Otherwise, assign the number and filter winning boards.
-        //
-        boards = boards
-            .into_iter()
-            .map(|mut board| {
-                board.assign_number(number);
-                board
-            })
-            .filter(|b| !b.has_won())
-            .collect();
+            let sum = board.unmarked_number_sum();
+            wins.push(Win {
+                draw_index,
+                number,
+                board_index,
+                score: u128::from(number) * u128::from(sum),
+            });
+            false
+        });
     }
 
-    None
+    Timeline { wins }
 }
 
 #[aoc(day4, part1)]
-fn solve_part_1(input: &(Vec<u8>, Vec<BingoBoard>)) -> u32 {
-    let (drawn_numbers, mut boards) = input.clone();
+fn solve_part_1(input: &(Vec<u32>, Vec<BingoBoard>)) -> Option<u128> {
+    let (drawn_numbers, boards) = input;
 
-    let (last_num, winner) =
-        find_winner(drawn_numbers.as_slice(), boards.as_mut_slice()).unwrap();
-
-    (last_num as usize * winner.unmarked_number_sum()) as u32
+    play(drawn_numbers, boards).first().map(|win| win.score)
 }
 
 #[aoc(day4, part2)]
-fn solve_part_2(input: &(Vec<u8>, Vec<BingoBoard>)) -> u32 {
-    let (drawn_numbers, boards) = input.clone();
-
-    let (last_num, last_board) =
-        find_last_board(drawn_numbers, boards).unwrap();
+fn solve_part_2(input: &(Vec<u32>, Vec<BingoBoard>)) -> Option<u128> {
+    let (drawn_numbers, boards) = input;
 
-    (last_num as usize * last_board.unmarked_number_sum()) as u32
+    play(drawn_numbers, boards).last().map(|win| win.score)
 }
 
 #[cfg(test)]
@@ -192,19 +408,215 @@ mod tests {
         let board = "22 13 17 11  0\n 8  2 23  4 24\n21  9 14 16  7\n 6 10  3 \
                      18  5\n 1 12 20 15 19";
         let lines = board.lines().collect::<Vec<_>>();
-        let mut board = BingoBoard::new(&lines);
+        let mut board = BingoBoard::new(&lines).unwrap();
 
         board.assign_number(16);
 
-        assert_eq!(board.get_column(0), vec![22, 8, 21, 6, 1]);
-        assert_eq!(board.0[2][3], MARKER);
+        assert_eq!(board.get_column(0), [22, 8, 21, 6, 1].map(Some));
+        assert_eq!(board.0[(2, 3)], None);
+    }
+
+    #[test]
+    fn test_board_size() {
+        let (drawn_numbers, boards) =
+            parse_input("2,4,6\n\n1 2\n3 4\n5 6\n\n1 2 3\n4 5 6").unwrap();
+
+        assert_eq!((boards[0].width(), boards[0].height()), (2, 3));
+        assert_eq!((boards[1].width(), boards[1].height()), (3, 2));
+
+        // The drawn numbers complete the second column of the first board,
+        // but they are spread over all columns of the second board.
+        //
+        assert_eq!(play(&drawn_numbers, &boards).wins, [Win {
+            draw_index: 2,
+            number: 6,
+            board_index: 0,
+            score: 6 * 9
+        }]);
+
+        let mut boards = mark(&drawn_numbers, &boards);
+        assert_eq!(boards[0].get_column(1), [None; 3]);
+        assert!(!boards[1].has_won());
+
+        // Completing the last row also works for the wider board.
+        //
+        boards[1].assign_number(5);
+        assert!(boards[1].has_won());
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let (drawn_numbers, boards) =
+            parse_input("300,255\n\n255 300\n1 2").unwrap();
+        assert!(!boards[0].has_won());
+
+        let mut board = boards[0].clone();
+        board.assign_number(300);
+        assert_eq!(board.to_string(), "[255, x]\n[1, 2]\n");
+
+        assert_eq!(play(&drawn_numbers, &boards).wins, [Win {
+            draw_index: 1,
+            number: 255,
+            board_index: 0,
+            score: 255 * 3
+        }]);
+
+        // The score doesn't fit in 32 bits.
+        //
+        let input = "4000000000,4000000001\n\n\
+                     4000000000 4000000001\n\
+                     4000000002 4000000003";
+        let (drawn_numbers, boards) = parse_input(input).unwrap();
+        assert_eq!(
+            play(&drawn_numbers, &boards).first().map(|win| win.score),
+            Some(4_000_000_001 * 8_000_000_005)
+        );
+    }
+
+    /// Marks the numbers on copies of the boards.
+    fn mark(drawn_numbers: &[u32], boards: &[BingoBoard]) -> Vec<BingoBoard> {
+        let mut boards = boards.to_vec();
+        for board in &mut boards {
+            for &number in drawn_numbers {
+                board.assign_number(number);
+            }
+        }
+
+        boards
+    }
+
+    fn get_square_board() -> BingoBoard {
+        BingoBoard::new(&["1 2 3", "4 5 6", "7 8 9"]).unwrap()
+    }
+
+    /// Whether the board wins with the pattern once the numbers have been
+    /// drawn.
+    fn wins(pattern: &WinPattern, numbers: &[u32]) -> bool {
+        mark(numbers, &[get_square_board()])[0].has_won_with(pattern)
+    }
+
+    #[test]
+    fn test_rows_and_columns() {
+        let pattern = WinPattern::RowsAndColumns;
+        assert_eq!(pattern, WinPattern::default());
+
+        assert!(wins(&pattern, &[4, 5, 6]));
+        assert!(wins(&pattern, &[3, 6, 9]));
+        assert!(!wins(&pattern, &[1, 5, 9]));
+        assert!(!wins(&pattern, &[1, 2, 4, 5]));
+    }
+
+    #[test]
+    fn test_diagonals() {
+        let pattern = WinPattern::Diagonals;
+
+        assert!(wins(&pattern, &[1, 5, 9]));
+        assert!(wins(&pattern, &[7, 5, 3]));
+        assert!(!wins(&pattern, &[1, 2, 3]));
+        assert!(!wins(&pattern, &[1, 5, 6]));
+
+        // Boards that aren't square don't have diagonals.
+        //
+        let board = BingoBoard::new(&["1 2 3", "4 5 6"]).unwrap();
+        assert_eq!(pattern.masks(board.width(), board.height()), []);
+    }
+
+    #[test]
+    fn test_four_corners() {
+        let pattern = WinPattern::FourCorners;
+
+        assert!(wins(&pattern, &[1, 3, 7, 9]));
+        assert!(!wins(&pattern, &[1, 2, 3, 7]));
+        assert!(!wins(&pattern, &[1, 4, 7]));
+    }
+
+    #[test]
+    fn test_blackout() {
+        let pattern = WinPattern::Blackout;
+
+        assert!(wins(&pattern, &[9, 8, 7, 6, 5, 4, 3, 2, 1]));
+        assert!(!wins(&pattern, &[1, 2, 3, 4, 5, 6, 7, 8]));
+    }
+
+    #[test]
+    fn test_custom() {
+        // A plus sign through the center.
+        //
+        let cells = [(0, 1), (1, 0), (1, 1), (1, 2), (2, 1)];
+        let plus = Mask::from_cells(3, 3, cells);
+        assert!(plus.contains(4));
+        assert!(!plus.contains(0));
+
+        let pattern = WinPattern::Custom(vec![plus]);
+        assert!(wins(&pattern, &[2, 4, 5, 6, 8]));
+        assert!(!wins(&pattern, &[4, 5, 6]));
+
+        let pattern = WinPattern::Any(vec![
+            WinPattern::FourCorners,
+            WinPattern::Diagonals,
+        ]);
+        assert!(wins(&pattern, &[1, 3, 7, 9]));
+        assert!(wins(&pattern, &[3, 5, 7]));
+        assert!(!wins(&pattern, &[1, 2, 3]));
+    }
+
+    #[test]
+    #[should_panic(expected = "different size")]
+    fn test_custom_size_mismatch() {
+        let mask = Mask::from_cells(4, 4, [(0, 0), (3, 3)]);
+        wins(&WinPattern::Custom(vec![mask]), &[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "mask is empty")]
+    fn test_custom_empty() {
+        wins(&WinPattern::Custom(vec![Mask::new(3, 3)]), &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "different sizes")]
+    fn test_subset_size_mismatch() {
+        Mask::new(2, 3).is_subset(&Mask::new(3, 2));
+    }
+
+    #[test]
+    fn test_play_with() {
+        let (drawn_numbers, boards) =
+            parse_input(include_str!("../input/2021/day4_example.txt"))
+                .unwrap();
+        let board_order = |timeline: Timeline| {
+            timeline
+                .wins
+                .iter()
+                .map(|win| (win.draw_index, win.board_index))
+                .collect_vec()
+        };
+
+        // The third board completes a diagonal before any row.
+        //
+        let pattern = WinPattern::Any(vec![
+            WinPattern::RowsAndColumns,
+            WinPattern::Diagonals,
+        ]);
+        let timeline = play_with(&drawn_numbers, &boards, &pattern);
+        assert_eq!(timeline.first().map(|win| win.score), Some(2 * 247));
+        assert_eq!(board_order(timeline), [(7, 2), (13, 0), (14, 1)]);
+
+        // Every number is drawn eventually, so nothing is left unmarked.
+        //
+        let pattern = WinPattern::Blackout;
+        let timeline = play_with(&drawn_numbers, &boards, &pattern);
+        assert!(timeline.wins.iter().all(|win| win.score == 0));
+        assert_eq!(board_order(timeline), [(24, 1), (25, 2), (26, 0)]);
     }
 
     #[test]
     fn test_max_unmarked_cells() {
-        let (drawn_numbers, mut boards) =
-            parse_input(include_str!("../input/2021/day4_example.txt"));
-        let _ = find_winner(drawn_numbers.as_slice(), boards.as_mut_slice());
+        let (drawn_numbers, boards) =
+            parse_input(include_str!("../input/2021/day4_example.txt"))
+                .unwrap();
+        let first = play(&drawn_numbers, &boards).wins[0];
+        let boards = mark(&drawn_numbers[..=first.draw_index], &boards);
 
         assert_eq!(boards[0].max_cells_marked(), 4);
         assert_eq!(boards[1].max_cells_marked(), 3);
@@ -212,42 +624,92 @@ mod tests {
     }
 
     #[test]
-    fn test_find_winner() {
-        let (drawn_numbers, mut boards) =
-            parse_input(include_str!("../input/2021/day4_example.txt"));
-        let (last_num, winner) =
-            find_winner(drawn_numbers.as_slice(), boards.as_mut_slice())
+    fn test_timeline() {
+        let (drawn_numbers, boards) =
+            parse_input(include_str!("../input/2021/day4_example.txt"))
                 .unwrap();
-
-        assert_eq!(last_num, 24);
-        assert_eq!(winner.unmarked_number_sum(), 188);
+        let timeline = play(&drawn_numbers, &boards);
+
+        assert_eq!(timeline.wins.len(), 3);
+        assert_eq!(timeline.first(), Some(&Win {
+            draw_index: 11,
+            number: 24,
+            board_index: 2,
+            score: 24 * 188
+        }));
+        assert_eq!(timeline.winner(1).map(|win| win.board_index), Some(0));
+        assert_eq!(timeline.last(), Some(&Win {
+            draw_index: 14,
+            number: 13,
+            board_index: 1,
+            score: 13 * 148
+        }));
+        assert_eq!(timeline.winner(3), None);
+
+        // Boards that never win don't show up.
+        //
+        let timeline = play(&drawn_numbers[..11], &boards);
+        assert_eq!(timeline, Timeline::default());
     }
 
     #[test]
     fn test_example() {
         let (drawn_numbers, boards) =
-            parse_input(include_str!("../input/2021/day4_example.txt"));
+            parse_input(include_str!("../input/2021/day4_example.txt"))
+                .unwrap();
         let part_1 = solve_part_1(&(drawn_numbers, boards));
 
         let (drawn_numbers, boards) =
-            parse_input(include_str!("../input/2021/day4_example.txt"));
+            parse_input(include_str!("../input/2021/day4_example.txt"))
+                .unwrap();
         let part_2 = solve_part_2(&(drawn_numbers, boards));
 
-        assert_eq!(part_1, 4512);
-        assert_eq!(part_2, 1924);
+        assert_eq!(part_1, Some(4512));
+        assert_eq!(part_2, Some(1924));
     }
 
     #[test]
     fn test_real() {
         let (drawn_numbers, boards) =
-            parse_input(include_str!("../input/2021/day4.txt"));
+            parse_input(include_str!("../input/2021/day4.txt")).unwrap();
         let part_1 = solve_part_1(&(drawn_numbers, boards));
 
         let (drawn_numbers, boards) =
-            parse_input(include_str!("../input/2021/day4.txt"));
+            parse_input(include_str!("../input/2021/day4.txt")).unwrap();
         let part_2 = solve_part_2(&(drawn_numbers, boards));
 
-        assert_eq!(part_1, 27027);
-        assert_eq!(part_2, 36975);
+        assert_eq!(part_1, Some(27027));
+        assert_eq!(part_2, Some(36975));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let error = parse_input("1,2,x").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidNumber);
+        assert_eq!((error.line, error.column), (1, 5));
+
+        // A row with a missing cell
+        //
+        let error = parse_input(
+            "1,2\n\n1 2 3 4 5\n1 2 3 4 5\n1 2 3 4\n1 2 3 4 5\n1 2 3 4 5",
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedLength {
+            expected: 5,
+            found: 4
+        });
+        assert_eq!(error.line, 5);
+
+        // A row that is longer than the first one
+        //
+        let error = parse_input("1,2\n\n1 2\n3 4\n\n1 2\n3 4 5").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedLength {
+            expected: 2,
+            found: 3
+        });
+        assert_eq!(error.line, 7);
+
+        let error = parse_input("").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedEnd);
     }
 }
@@ -1,5 +1,6 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Itertools;
+use crate::error::{parse_number, ParseError, ParseErrorKind};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Copy, Clone)]
 pub struct Line {
@@ -7,17 +8,193 @@ pub struct Line {
     pub to: (u32, u32),
 }
 
+impl Line {
+    pub fn is_straight(&self) -> bool {
+        self.from.0 == self.to.0 || self.from.1 == self.to.1
+    }
+
+    /// Whether the line is at exactly 45 degrees.
+    pub fn is_diagonal(&self) -> bool {
+        let dx = self.from.0.abs_diff(self.to.0);
+        let dy = self.from.1.abs_diff(self.to.1);
+
+        !self.is_straight() && dx == dy
+    }
+
+    /// Rasterises the line from `from` to `to` with Bresenham's algorithm,
+    /// which works for any slope.
+    pub fn points(&self) -> impl Iterator<Item = (u32, u32)> {
+        let (mut x, mut y) = (self.from.0 as i64, self.from.1 as i64);
+        let (to_x, to_y) = (self.to.0 as i64, self.to.1 as i64);
+
+        let (dx, dy) = ((to_x - x).abs(), -(to_y - y).abs());
+        let (step_x, step_y) = ((to_x - x).signum(), (to_y - y).signum());
+        let mut error = dx + dy;
+        let mut done = false;
+
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+
+            let point = (x as u32, y as u32);
+            if x == to_x && y == to_y {
+                done = true;
+                return Some(point);
+            }
+
+            let error2 = 2 * error;
+            if error2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+
+            Some(point)
+        })
+    }
+}
+
+/// Bounding boxes with at most this many cells are counted in a dense buffer.
+const MAX_DENSE_CELLS: u64 = 1 << 22;
+
+/// The number of lines covering each point.
+enum Counter {
+    Sparse(HashMap<(u32, u32), u32>),
+    Dense {
+        min: (u32, u32),
+        width: u32,
+        height: u32,
+        counts: Vec<u32>,
+    },
+}
+
+impl Default for Counter {
+    fn default() -> Self { Counter::Sparse(HashMap::new()) }
+}
+
 #[derive(Default)]
 pub struct Diagram {
-    // TODO: Replace this with hashmap to make it more efficient
-    pub points: Vec<(u32, u32)>,
+    counter: Counter,
 }
 
 impl Diagram {
+    /// Creates a diagram backed by a hash map, which works for any
+    /// coordinates.
     pub fn new() -> Self { Default::default() }
 
+    /// Creates a diagram suited for drawing the lines: if their bounding box
+    /// is small enough, a dense buffer is used instead of a hash map.
+    pub fn for_lines(lines: &[Line]) -> Self {
+        if lines.is_empty() {
+            return Self::new();
+        }
+
+        let min_x = lines.iter().map(|l| l.from.0.min(l.to.0)).min().unwrap();
+        let max_x = lines.iter().map(|l| l.from.0.max(l.to.0)).max().unwrap();
+        let min_y = lines.iter().map(|l| l.from.1.min(l.to.1)).min().unwrap();
+        let max_y = lines.iter().map(|l| l.from.1.max(l.to.1)).max().unwrap();
+
+        let width = (max_x - min_x) as u64 + 1;
+        let height = (max_y - min_y) as u64 + 1;
+        if width * height > MAX_DENSE_CELLS {
+            return Self::new();
+        }
+        let (width, height) = (width as u32, height as u32);
+
+        Self {
+            counter: Counter::Dense {
+                min: (min_x, min_y),
+                width,
+                height,
+                counts: vec![0; (width * height) as usize],
+            },
+        }
+    }
+
+    fn mark(&mut self, point: (u32, u32)) {
+        if let Counter::Dense {
+            min,
+            width,
+            height,
+            counts,
+        } = &mut self.counter
+        {
+            match dense_index(point, *min, *width, *height) {
+                Some(index) => {
+                    counts[index] += 1;
+                    return;
+                }
+                None => {
+                    // The point is outside of the bounding box, so fall back
+                    // to the sparse representation.
+                    //
+                    let points = self.points().collect();
+                    self.counter = Counter::Sparse(points);
+                }
+            }
+        }
+
+        if let Counter::Sparse(points) = &mut self.counter {
+            *points.entry(point).or_default() += 1;
+        }
+    }
+
+    /// The number of lines covering the point.
+    pub fn count(&self, point: (u32, u32)) -> u32 {
+        match &self.counter {
+            Counter::Sparse(points) => {
+                points.get(&point).copied().unwrap_or_default()
+            }
+            Counter::Dense {
+                min,
+                width,
+                height,
+                counts,
+            } => dense_index(point, *min, *width, *height)
+                .map_or(0, |index| counts[index]),
+        }
+    }
+
+    /// All covered points together with the number of lines covering them.
+    pub fn points(&self) -> Box<dyn Iterator<Item = ((u32, u32), u32)> + '_> {
+        match &self.counter {
+            Counter::Sparse(points) => {
+                Box::new(points.iter().map(|(&point, &count)| (point, count)))
+            }
+            Counter::Dense {
+                min, width, counts, ..
+            } => Box::new(
+                counts
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &count)| count > 0)
+                    .map(move |(index, &count)| {
+                        let x = min.0 + index as u32 % width;
+                        let y = min.1 + index as u32 / width;
+                        ((x, y), count)
+                    }),
+            ),
+        }
+    }
+
+    /// The number of points that are covered by at least `n` lines.
+    pub fn covered_by_at_least(&self, n: u32) -> u32 {
+        match &self.counter {
+            Counter::Sparse(points) => {
+                points.values().filter(|&&count| count >= n).count() as u32
+            }
+            Counter::Dense { counts, .. } => {
+                counts.iter().filter(|&&count| count >= n).count() as u32
+            }
+        }
+    }
+
     fn draw_diagonal(&mut self, line: &Line) {
-        if line.from.0 != line.to.0 && line.from.1 != line.to.1 {
+        if line.is_diagonal() {
             let mut x_range: Vec<_> = (line.from.0..=line.to.0).collect();
             if line.from.0 > line.to.0 {
                 x_range = (line.to.0..=line.from.0).rev().collect();
@@ -40,7 +217,9 @@ impl Diagram {
                         None
                     }
                 });
-            self.points.extend(points);
+            for point in points {
+                self.mark(point);
+            }
         }
     }
 
@@ -50,7 +229,7 @@ impl Diagram {
             let end = line.from.0.max(line.to.0);
 
             for p in start..=end {
-                self.points.push((p, line.to.1));
+                self.mark((p, line.to.1));
             }
         }
     }
@@ -61,7 +240,7 @@ impl Diagram {
             let end = line.from.1.max(line.to.1);
 
             for p in start..=end {
-                self.points.push((line.from.0, p));
+                self.mark((line.from.0, p));
             }
         }
     }
@@ -74,46 +253,293 @@ impl Diagram {
         }
     }
 
+    fn draw_bresenham(&mut self, line: &Line) {
+        for point in line.points() {
+            self.mark(point);
+        }
+    }
+
+    /// Draws a line of any slope. Straight and 45 degree lines take a faster
+    /// path than the general rasterisation.
     pub fn draw_line(&mut self, line: &Line) {
-        if line.from.0 != line.to.0 && line.from.1 != line.to.1 {
+        if line.is_straight() {
+            self.draw_straight_line(line);
+        } else if line.is_diagonal() {
             self.draw_diagonal(line);
         } else {
-            self.draw_straight_line(line);
+            self.draw_bresenham(line);
+        }
+    }
+
+    pub fn duplicates(&self) -> u32 { self.covered_by_at_least(2) }
+}
+
+fn dense_index(
+    point: (u32, u32), min: (u32, u32), width: u32, height: u32,
+) -> Option<usize> {
+    let x = point.0.checked_sub(min.0).filter(|&x| x < width)?;
+    let y = point.1.checked_sub(min.1).filter(|&y| y < height)?;
+
+    Some((y * width + x) as usize)
+}
+
+/// The infinite line that a segment lies on, identified by its reduced
+/// direction and the cross product of that direction with any of its points.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Carrier {
+    step: (i64, i64),
+    offset: i128,
+}
+
+impl Carrier {
+    fn through(step: (i64, i64), point: (i64, i64)) -> Self {
+        Self {
+            step,
+            offset: cross(step, point),
+        }
+    }
+
+    /// Position of a lattice point on the carrier. Neighbouring lattice points
+    /// have neighbouring positions.
+    fn position(&self, point: (i64, i64)) -> i64 {
+        if self.step.0 != 0 {
+            point.0.div_euclid(self.step.0)
+        } else {
+            point.1
         }
     }
+}
 
-    pub fn duplicates(&self) -> u32 {
-        self.points.iter().duplicates().count() as u32
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
     }
 }
 
+fn to_i64(point: (u32, u32)) -> (i64, i64) { (point.0 as i64, point.1 as i64) }
+
+/// The cross product of two vectors. The coordinates can be close to
+/// `u32::MAX`, so the products don't fit into an `i64`.
+fn cross(a: (i64, i64), b: (i64, i64)) -> i128 {
+    a.0 as i128 * b.1 as i128 - a.1 as i128 * b.0 as i128
+}
+
+impl Line {
+    /// The reduced direction of the line, normalised so that parallel lines
+    /// share it. Single points are treated as horizontal.
+    fn step(&self) -> (i64, i64) {
+        let (from, to) = (to_i64(self.from), to_i64(self.to));
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+
+        let divisor = gcd(dx, dy);
+        if divisor == 0 {
+            return (1, 0);
+        }
+
+        let (dx, dy) = (dx / divisor, dy / divisor);
+        if dx < 0 || (dx == 0 && dy < 0) {
+            (-dx, -dy)
+        } else {
+            (dx, dy)
+        }
+    }
+
+    fn carrier(&self) -> Carrier {
+        Carrier::through(self.step(), to_i64(self.from))
+    }
+
+    /// Whether the lattice point lies on the segment.
+    fn contains(&self, point: (i64, i64)) -> bool {
+        let (from, to) = (to_i64(self.from), to_i64(self.to));
+        let direction = (to.0 - from.0, to.1 - from.1);
+
+        cross(direction, (point.0 - from.0, point.1 - from.1)) == 0
+            && from.0.min(to.0) <= point.0
+            && point.0 <= from.0.max(to.0)
+            && from.1.min(to.1) <= point.1
+            && point.1 <= from.1.max(to.1)
+    }
+
+    /// The lattice point where two non-collinear segments cross, if any.
+    fn crossing(&self, other: &Line) -> Option<(i64, i64)> {
+        if self.from == self.to {
+            let point = to_i64(self.from);
+            return other.contains(point).then_some(point);
+        }
+        if other.from == other.to {
+            return other.crossing(self);
+        }
+
+        let (p, q) = (to_i64(self.from), to_i64(other.from));
+        let r = (self.to.0 as i64 - p.0, self.to.1 as i64 - p.1);
+        let v = (other.to.0 as i64 - q.0, other.to.1 as i64 - q.1);
+
+        let denominator = cross(r, v);
+        if denominator == 0 {
+            return None;
+        }
+
+        // The crossing is at `p + r * numerator / denominator`, which has to
+        // be a lattice point.
+        //
+        let numerator = cross((q.0 - p.0, q.1 - p.1), v);
+        let x = numerator * r.0 as i128;
+        let y = numerator * r.1 as i128;
+        if x % denominator != 0 || y % denominator != 0 {
+            return None;
+        }
+
+        let point = (
+            p.0 + (x / denominator) as i64,
+            p.1 + (y / denominator) as i64,
+        );
+        (self.contains(point) && other.contains(point)).then_some(point)
+    }
+}
+
+/// Counts the lattice points that are covered by at least two lines, without
+/// rasterising them. This only agrees with [`Diagram::duplicates`] for
+/// straight and diagonal lines, since Bresenham also covers points that
+/// aren't exactly on the line.
+///
+/// Collinear lines are grouped by their carrier and their overlaps found with
+/// a sweep over the positions on it. Crossings of all other pairs are found
+/// with a sweep over the x-axis, which only compares lines whose x-ranges
+/// overlap.
+pub fn count_overlaps(lines: &[Line]) -> u64 {
+    // Collinear overlaps
+    //
+    let mut carriers: HashMap<Carrier, Vec<(i64, i64)>> = HashMap::new();
+    for line in lines {
+        let carrier = line.carrier();
+        let from = carrier.position(to_i64(line.from));
+        let to = carrier.position(to_i64(line.to));
+
+        carriers
+            .entry(carrier)
+            .or_default()
+            .push((from.min(to), from.max(to)));
+    }
+
+    let mut count = 0;
+    let mut overlaps: HashMap<Carrier, Vec<(i64, i64)>> = HashMap::new();
+    for (carrier, intervals) in carriers {
+        let mut events = intervals
+            .iter()
+            .flat_map(|&(start, end)| [(start, 1), (end + 1, -1)])
+            .collect::<Vec<_>>();
+        events.sort_unstable();
+
+        let mut depth = 0;
+        let mut covered = Vec::new();
+        for (position, change) in events {
+            let was_overlapping = depth >= 2;
+            depth += change;
+
+            match (was_overlapping, depth >= 2) {
+                (false, true) => covered.push((position, position)),
+                (true, false) => covered.last_mut().unwrap().1 = position - 1,
+                _ => {}
+            }
+        }
+
+        covered.retain(|&(start, end)| start <= end);
+        count += covered
+            .iter()
+            .map(|&(start, end)| (end - start + 1) as u64)
+            .sum::<u64>();
+        if !covered.is_empty() {
+            overlaps.insert(carrier, covered);
+        }
+    }
+
+    // Crossings of lines on different carriers
+    //
+    let mut order = (0..lines.len()).collect::<Vec<_>>();
+    order.sort_unstable_by_key(|&i| lines[i].from.0.min(lines[i].to.0));
+
+    let mut active: Vec<usize> = Vec::new();
+    let mut crossings = HashSet::new();
+    for i in order {
+        let line = &lines[i];
+        let min_x = line.from.0.min(line.to.0);
+        active.retain(|&j| lines[j].from.0.max(lines[j].to.0) >= min_x);
+
+        let carrier = line.carrier();
+        for &j in &active {
+            if lines[j].carrier() != carrier {
+                crossings.extend(line.crossing(&lines[j]));
+            }
+        }
+        active.push(i);
+    }
+
+    // Crossings on a collinear overlap have already been counted. Points
+    // where overlaps of several carriers meet are always crossings too, but
+    // have been counted once per carrier.
+    //
+    let steps = overlaps.keys().map(|c| c.step).collect::<HashSet<_>>();
+    for point in crossings {
+        let carriers = steps
+            .iter()
+            .filter(|&&step| {
+                let carrier = Carrier::through(step, point);
+                let position = carrier.position(point);
+
+                overlaps.get(&carrier).is_some_and(|covered| {
+                    covered.iter().any(|&(start, end)| {
+                        start <= position && position <= end
+                    })
+                })
+            })
+            .count() as u64;
+
+        if carriers == 0 {
+            count += 1;
+        } else {
+            count -= carriers - 1;
+        }
+    }
+
+    count
+}
+
+fn parse_point(
+    line_index: usize, line: &str, text: &str,
+) -> Result<(u32, u32), ParseError> {
+    let (x, y) = text.split_once(',').ok_or_else(|| {
+        ParseError::new(ParseErrorKind::MissingField, line_index, line, text)
+    })?;
+
+    Ok((
+        parse_number(line_index, line, x)?,
+        parse_number(line_index, line, y)?,
+    ))
+}
+
 #[aoc_generator(day5)]
-fn parse_input(input: &str) -> Vec<Line> {
+fn parse_input(input: &str) -> Result<Vec<Line>, ParseError> {
     input
         .lines()
-        .map(|line| {
-            let parts = line.split(" -> ").collect::<Vec<_>>();
+        .enumerate()
+        .map(|(i, line)| {
+            let (from, to) = line.split_once(" -> ").ok_or_else(|| {
+                ParseError::new(ParseErrorKind::MissingField, i, line, line)
+            })?;
 
-            let from = parts[0]
-                .split(',')
-                .map(|x| x.parse::<u32>().unwrap())
-                .collect::<Vec<_>>();
-            let to = parts[1]
-                .split(',')
-                .map(|x| x.parse::<u32>().unwrap())
-                .collect::<Vec<_>>();
-
-            Line {
-                from: (from[0], from[1]),
-                to: (to[0], to[1]),
-            }
+            Ok(Line {
+                from: parse_point(i, line, from)?,
+                to: parse_point(i, line, to)?,
+            })
         })
-        .collect::<Vec<_>>()
+        .collect()
 }
 
 #[aoc(day5, part1)]
 fn solve_part_1(input: &[Line]) -> u32 {
-    let mut diagram = Diagram::new();
+    let mut diagram = Diagram::for_lines(input);
     for line in input {
         diagram.draw_straight_line(line);
     }
@@ -123,7 +549,7 @@ fn solve_part_1(input: &[Line]) -> u32 {
 
 #[aoc(day5, part2)]
 fn solve_part_2(input: &[Line]) -> u32 {
-    let mut diagram = Diagram::new();
+    let mut diagram = Diagram::for_lines(input);
     for line in input {
         diagram.draw_line(line);
     }
@@ -131,15 +557,36 @@ fn solve_part_2(input: &[Line]) -> u32 {
     diagram.duplicates()
 }
 
+#[aoc(day5, part1, analytic)]
+fn solve_part_1_analytic(input: &[Line]) -> u64 {
+    let lines = input
+        .iter()
+        .filter(|line| line.is_straight())
+        .copied()
+        .collect::<Vec<_>>();
+
+    count_overlaps(&lines)
+}
+
+#[aoc(day5, part2, analytic)]
+fn solve_part_2_analytic(input: &[Line]) -> u64 { count_overlaps(input) }
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::Itertools;
+    use proptest::prelude::*;
+    use test::Bencher;
 
     fn get_input() -> &'static str {
         "0,9 -> 5,9\n8,0 -> 0,8\n9,4 -> 3,4\n2,2 -> 2,1\n7,0 -> 7,4\n6,4 -> \
          2,0\n0,9 -> 2,9\n3,4 -> 1,4\n0,0 -> 8,8\n5,5 -> 8,2"
     }
 
+    fn covered_points(diagram: &Diagram) -> Vec<(u32, u32)> {
+        diagram.points().map(|(point, _)| point).sorted().collect()
+    }
+
     #[test]
     fn test_draw_line() {
         // Horizontal line
@@ -149,7 +596,7 @@ mod tests {
             from: (9, 7),
             to: (7, 7),
         });
-        assert_eq!(diagram.points, vec![(7, 7), (8, 7), (9, 7)]);
+        assert_eq!(covered_points(&diagram), [(7, 7), (8, 7), (9, 7)]);
 
         // Vertical line
         //
@@ -158,7 +605,7 @@ mod tests {
             from: (1, 1),
             to: (1, 3),
         });
-        assert_eq!(diagram.points, vec![(1, 1), (1, 2), (1, 3)]);
+        assert_eq!(covered_points(&diagram), [(1, 1), (1, 2), (1, 3)]);
 
         // Diagonal line
         //
@@ -167,19 +614,104 @@ mod tests {
             from: (1, 1),
             to: (3, 3),
         });
-        assert_eq!(diagram.points, vec![(1, 1), (2, 2), (3, 3)]);
+        assert_eq!(covered_points(&diagram), [(1, 1), (2, 2), (3, 3)]);
 
         let mut diagram = Diagram::new();
         diagram.draw_line(&Line {
             from: (9, 7),
             to: (7, 9),
         });
-        assert_eq!(diagram.points, vec![(9, 7), (8, 8), (7, 9)]);
+        assert_eq!(covered_points(&diagram), [(7, 9), (8, 8), (9, 7)]);
+
+        // Arbitrary slope
+        //
+        let mut diagram = Diagram::new();
+        diagram.draw_line(&Line {
+            from: (0, 1),
+            to: (6, 4),
+        });
+        assert_eq!(covered_points(&diagram), [
+            (0, 1),
+            (1, 2),
+            (2, 2),
+            (3, 3),
+            (4, 3),
+            (5, 4),
+            (6, 4)
+        ]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_points_include_endpoints(
+            from in (0u32..1000, 0u32..1000), to in (0u32..1000, 0u32..1000),
+        ) {
+            let line = Line { from, to };
+            let points = line.points().collect_vec();
+
+            prop_assert_eq!(points.first(), Some(&from));
+            prop_assert_eq!(points.last(), Some(&to));
+        }
+
+        #[test]
+        fn test_point_count(
+            from in (0u32..1000, 0u32..1000), to in (0u32..1000, 0u32..1000),
+        ) {
+            let line = Line { from, to };
+            let expected = from.0.abs_diff(to.0).max(from.1.abs_diff(to.1)) + 1;
+
+            let mut diagram = Diagram::new();
+            diagram.draw_line(&line);
+
+            prop_assert_eq!(line.points().count() as u32, expected);
+            prop_assert_eq!(diagram.covered_by_at_least(1), expected);
+            prop_assert_eq!(diagram.duplicates(), 0);
+        }
+    }
+
+    #[test]
+    fn test_counts() {
+        let lines = parse_input(get_input()).unwrap();
+
+        let mut sparse = Diagram::new();
+        let mut dense = Diagram::for_lines(&lines);
+        for line in lines.iter() {
+            sparse.draw_line(line);
+            dense.draw_line(line);
+        }
+
+        for diagram in [&sparse, &dense] {
+            assert_eq!(diagram.count((0, 0)), 1);
+            assert_eq!(diagram.count((4, 4)), 3);
+            assert_eq!(diagram.count((9, 9)), 0);
+            assert_eq!(diagram.count((100, 100)), 0);
+            assert_eq!(diagram.covered_by_at_least(1), 39);
+            assert_eq!(diagram.covered_by_at_least(3), 2);
+        }
+        assert_eq!(covered_points(&sparse), covered_points(&dense));
+    }
+
+    #[test]
+    fn test_dense_fallback() {
+        let line = Line {
+            from: (0, 0),
+            to: (2, 0),
+        };
+        let mut diagram = Diagram::for_lines(&[line]);
+        diagram.draw_line(&line);
+        diagram.draw_line(&Line {
+            from: (2, 0),
+            to: (2, 5),
+        });
+
+        assert_eq!(diagram.count((2, 0)), 2);
+        assert_eq!(diagram.count((2, 5)), 1);
+        assert_eq!(diagram.duplicates(), 1);
     }
 
     #[test]
     fn test_example() {
-        let lines = parse_input(get_input());
+        let lines = parse_input(get_input()).unwrap();
 
         // Part 1
         let mut diagram = Diagram::new();
@@ -196,13 +728,187 @@ mod tests {
         assert_eq!(diagram.duplicates(), 12);
     }
 
+    #[test]
+    fn test_count_overlaps() {
+        let lines = parse_input(get_input()).unwrap();
+
+        assert_eq!(solve_part_1_analytic(&lines), 5);
+        assert_eq!(solve_part_2_analytic(&lines), 12);
+
+        // Collinear overlaps that also cross other lines and overlaps
+        //
+        let lines = [
+            Line {
+                from: (0, 0),
+                to: (4, 0),
+            },
+            Line {
+                from: (2, 0),
+                to: (6, 0),
+            },
+            Line {
+                from: (3, 3),
+                to: (3, 0),
+            },
+            Line {
+                from: (3, 1),
+                to: (3, 0),
+            },
+            Line {
+                from: (0, 3),
+                to: (3, 0),
+            },
+            Line {
+                from: (5, 5),
+                to: (5, 5),
+            },
+            Line {
+                from: (0, 0),
+                to: (6, 6),
+            },
+        ];
+        let mut diagram = Diagram::new();
+        for line in &lines {
+            diagram.draw_line(line);
+        }
+        assert_eq!(count_overlaps(&lines), diagram.duplicates() as u64);
+    }
+
+    #[test]
+    fn test_count_overlaps_large() {
+        let lines = [
+            Line {
+                from: (0, 1_000_000),
+                to: (4_000_000, 1_000_000),
+            },
+            Line {
+                from: (3_000_000, 1_000_000),
+                to: (9_000_000, 1_000_000),
+            },
+            Line {
+                from: (0, 0),
+                to: (8_000_000, 8_000_000),
+            },
+            Line {
+                from: (2_500_000, 0),
+                to: (2_500_000, 5_000_000),
+            },
+        ];
+
+        // 1_000_001 collinear points, plus the crossings at (1e6, 1e6),
+        // (2.5e6, 1e6) and (2.5e6, 2.5e6).
+        //
+        assert_eq!(count_overlaps(&lines), 1_000_004);
+    }
+
+    #[test]
+    fn test_count_overlaps_extreme() {
+        const MAX: u32 = u32::MAX;
+        let line = |from, to| Line { from, to };
+        let lines = [
+            line((0, 0), (MAX - 1, MAX - 1)),
+            line((0, MAX - 1), (MAX - 1, 0)),
+            line((MAX - 3, MAX - 3), (MAX, MAX)),
+            line((MAX, 0), (0, MAX)),
+            line((MAX, MAX - 1), (0, 0)),
+        ];
+
+        // 3 collinear points at the end of the first line, plus the crossings
+        // at (0, 0) and in the middle of the first two lines.
+        //
+        assert_eq!(count_overlaps(&lines), 5);
+    }
+
+    proptest! {
+        #[test]
+        fn test_count_overlaps_matches_diagram(
+            lines in prop::collection::vec(
+                ((0u32..20, 0u32..20), 0u32..4, 0u32..10),
+                0..20,
+            )
+        ) {
+            // Only straight and diagonal lines are covered identically.
+            //
+            let lines = lines
+                .into_iter()
+                .map(|(from, direction, length)| {
+                    let to = match direction {
+                        0 => (from.0 + length, from.1),
+                        1 => (from.0, from.1 + length),
+                        2 => (from.0 + length, from.1 + length),
+                        _ => (from.0 + length, from.1.saturating_sub(length)),
+                    };
+                    Line { from, to }
+                })
+                .filter(|line| line.is_straight() || line.is_diagonal())
+                .collect::<Vec<_>>();
+
+            let mut diagram = Diagram::new();
+            for line in &lines {
+                diagram.draw_line(line);
+            }
+
+            let expected = diagram.duplicates() as u64;
+            prop_assert_eq!(count_overlaps(&lines), expected);
+        }
+    }
+
     #[test]
     fn test_real() {
-        let lines = parse_input(include_str!("../input/2021/day5.txt"));
+        let lines =
+            parse_input(include_str!("../input/2021/day5.txt")).unwrap();
         let part1 = solve_part_1(&lines);
         let part2 = solve_part_2(&lines);
 
         assert_eq!(part1, 6461);
         assert_eq!(part2, 18065);
+
+        assert_eq!(solve_part_1_analytic(&lines), part1 as u64);
+        assert_eq!(solve_part_2_analytic(&lines), part2 as u64);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let error = parse_input("0,9 -> 5,9\n8,0 -> 0;8").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::MissingField);
+        assert_eq!((error.line, error.column), (2, 8));
+        assert_eq!(error.text, "0;8");
+
+        let error = parse_input("0,9 -> 5,9\n8,0 => 0,8").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::MissingField);
+        assert_eq!(error.line, 2);
+
+        let error = parse_input("0,9 -> 5,-9").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidNumber);
+        assert_eq!((error.line, error.column), (1, 10));
+        assert_eq!(error.text, "-9");
+    }
+
+    #[bench]
+    fn bench_sparse(b: &mut Bencher) {
+        let lines =
+            parse_input(include_str!("../input/2021/day5.txt")).unwrap();
+
+        b.iter(|| {
+            let mut diagram = Diagram::new();
+            for line in lines.iter() {
+                diagram.draw_line(line);
+            }
+            diagram.duplicates()
+        });
+    }
+
+    #[bench]
+    fn bench_dense(b: &mut Bencher) {
+        let lines =
+            parse_input(include_str!("../input/2021/day5.txt")).unwrap();
+
+        b.iter(|| {
+            let mut diagram = Diagram::for_lines(&lines);
+            for line in lines.iter() {
+                diagram.draw_line(line);
+            }
+            diagram.duplicates()
+        });
     }
 }
@@ -1,59 +1,274 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::{collections::VecDeque, str::FromStr};
+use crate::{
+    error::{parse_number, ParseError},
+    grid::Grid,
+};
+use num_bigint::BigUint;
+use std::collections::VecDeque;
 
 #[aoc_generator(day6)]
-fn parse_input(input: &str) -> Vec<u8> {
+fn parse_input(input: &str) -> Result<Vec<u8>, ParseError> {
     input
         .lines()
-        .map(|line| line.split(',').filter_map(|num| u8::from_str(num).ok()))
-        .flatten()
-        .collect::<Vec<_>>()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            line.split(',').map(move |num| parse_number(i, line, num))
+        })
+        .collect()
 }
 
-fn input_to_array(input: &[u8]) -> VecDeque<u64> {
-    let mut array = VecDeque::from([0u64; 9]);
+/// The lifecycle of the lanternfish: after reaching zero, a fish resets its
+/// timer to `reset` and spawns a new fish with a timer of `newborn`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FishModel {
+    pub reset: u8,
+    pub newborn: u8,
+}
+
+impl Default for FishModel {
+    fn default() -> Self {
+        Self {
+            reset: 6,
+            newborn: 8,
+        }
+    }
+}
+
+impl FishModel {
+    /// The number of fish per timer value.
+    pub fn counts(&self, input: &[u8]) -> VecDeque<u64> {
+        let max_timer = input
+            .iter()
+            .copied()
+            .chain([self.reset, self.newborn])
+            .max()
+            .unwrap_or_default();
+        let mut array = VecDeque::from(vec![0u64; max_timer as usize + 1]);
+
+        for i in input {
+            array[(*i) as usize] += 1;
+        }
+
+        array
+    }
+
+    /// The number of fish per timer value for each day, starting with the
+    /// initial population.
+    pub fn snapshots(
+        &self, input: &[u8],
+    ) -> impl Iterator<Item = VecDeque<u64>> {
+        let model = *self;
+
+        std::iter::successors(Some(self.counts(input)), move |data| {
+            let mut data = data.clone();
+
+            let zero_states = data.pop_front().unwrap_or_default();
+            data.push_back(0);
+            data[model.reset as usize] += zero_states;
+            data[model.newborn as usize] += zero_states;
+
+            Some(data)
+        })
+    }
+
+    pub fn simulate(&self, input: &[u8], days: u64) -> u64 {
+        self.snapshots(input)
+            .nth(days as usize)
+            .unwrap_or_default()
+            .into_iter()
+            .sum::<u64>()
+    }
+
+    /// The matrix that maps the number of fish per timer to the numbers of
+    /// the next day.
+    fn transition_matrix<A: Arithmetic>(
+        &self, arithmetic: &A, size: usize,
+    ) -> Grid<A::Value> {
+        let mut matrix = Grid::new(size, size, arithmetic.zero());
+
+        // Every timer counts down, and fish at zero reset and spawn a new fish.
+        // Both can have the same timer, so they add up.
+        //
+        for timer in 1..size {
+            matrix[(timer - 1, timer)] = arithmetic.value(1);
+        }
+        for timer in [self.reset, self.newborn] {
+            let entry = &mut matrix[(timer as usize, 0)];
+            *entry = arithmetic.add(entry, &arithmetic.value(1));
+        }
+
+        matrix
+    }
+
+    /// Same as [`FishModel::simulate`], but in `O(log(days))` steps by
+    /// raising the transition matrix to the power of `days`.
+    pub fn simulate_matrix<A: Arithmetic>(
+        &self, input: &[u8], days: u64, arithmetic: &A,
+    ) -> A::Value {
+        let counts = self.counts(input);
+        let matrix = pow_matrix(
+            arithmetic,
+            &self.transition_matrix(arithmetic, counts.len()),
+            days,
+        );
+
+        matrix
+            .positions()
+            .fold(arithmetic.zero(), |acc, (row, col)| {
+                let count = arithmetic.value(counts[col]);
+                let product = arithmetic.mul(&matrix[(row, col)], &count);
+                arithmetic.add(&acc, &product)
+            })
+    }
+}
+
+/// The arithmetic used for the entries of the transition matrix.
+pub trait Arithmetic {
+    type Value: Clone;
+
+    fn zero(&self) -> Self::Value;
+    fn value(&self, value: u64) -> Self::Value;
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+    fn mul(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/// Arithmetic modulo the wrapped number.
+pub struct Modulo(pub u64);
+
+impl Arithmetic for Modulo {
+    type Value = u64;
+
+    fn zero(&self) -> u64 { 0 }
+
+    fn value(&self, value: u64) -> u64 { value % self.0 }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 + *b as u128) % self.0 as u128) as u64
+    }
+
+    fn mul(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 * *b as u128) % self.0 as u128) as u64
+    }
+}
+
+/// Arbitrary precision arithmetic. Note that the population grows
+/// exponentially, so the result for `n` days has roughly `n / 25` digits.
+pub struct Exact;
+
+impl Arithmetic for Exact {
+    type Value = BigUint;
+
+    fn zero(&self) -> BigUint { BigUint::default() }
+
+    fn value(&self, value: u64) -> BigUint { BigUint::from(value) }
 
-    for i in input {
-        array[(*i) as usize] += 1;
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint { a + b }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint { a * b }
+}
+
+fn mul_matrix<A: Arithmetic>(
+    arithmetic: &A, a: &Grid<A::Value>, b: &Grid<A::Value>,
+) -> Grid<A::Value> {
+    let mut result = Grid::new(b.width(), a.height(), arithmetic.zero());
+    for (row, col) in result.positions() {
+        result[(row, col)] = (0..a.width()).fold(arithmetic.zero(), |acc, i| {
+            arithmetic.add(&acc, &arithmetic.mul(&a[(row, i)], &b[(i, col)]))
+        });
     }
 
-    array
+    result
 }
 
-fn simulate(input: &[u8], days: u64) -> u64 {
-    let mut data = input_to_array(input);
+/// Computes `matrix ^ exponent` by repeated squaring.
+fn pow_matrix<A: Arithmetic>(
+    arithmetic: &A, matrix: &Grid<A::Value>, mut exponent: u64,
+) -> Grid<A::Value> {
+    let size = matrix.width();
+    let mut result = Grid::new(size, size, arithmetic.zero());
+    for i in 0..size {
+        result[(i, i)] = arithmetic.value(1);
+    }
 
-    // Simulate each day
-    for _ in 0..days {
-        let zero_states = data.pop_front().unwrap_or_default();
-        data[6] += zero_states;
-        data.push_back(zero_states);
+    let mut base = matrix.clone();
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_matrix(arithmetic, &result, &base);
+        }
+        base = mul_matrix(arithmetic, &base, &base);
+        exponent >>= 1;
     }
 
-    data.into_iter().sum::<u64>()
+    result
 }
 
 #[aoc(day6, part1)]
-fn solve_part_1(input: &[u8]) -> u64 { simulate(input, 80) }
+fn solve_part_1(input: &[u8]) -> u64 {
+    FishModel::default().simulate(input, 80)
+}
 
 #[aoc(day6, part2)]
-fn solve_part_2(input: &[u8]) -> u64 { simulate(input, 256) }
+fn solve_part_2(input: &[u8]) -> u64 {
+    FishModel::default().simulate(input, 256)
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ParseErrorKind;
 
     #[test]
-    fn test_input_to_array() {
-        let input = parse_input("3,4,3,1,2");
-        let array = input_to_array(&input);
+    fn test_counts() {
+        let input = parse_input("3,4,3,1,2").unwrap();
+        let array = FishModel::default().counts(&input);
 
         assert_eq!(array, vec![0, 1, 1, 2, 1, 0, 0, 0, 0]);
     }
 
+    #[test]
+    fn test_snapshots() {
+        let input = parse_input("3,4,3,1,2").unwrap();
+        let snapshots = FishModel::default()
+            .snapshots(&input)
+            .take(4)
+            .collect::<Vec<_>>();
+
+        assert_eq!(snapshots[1], vec![1, 1, 2, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(snapshots[2], vec![1, 2, 1, 0, 0, 0, 1, 0, 1]);
+        assert_eq!(snapshots[3], vec![2, 1, 0, 0, 0, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_custom_model() {
+        let input = parse_input("3,4,3,1,2").unwrap();
+
+        // Fish that reset immediately double every day.
+        //
+        let doubling = FishModel {
+            reset: 0,
+            newborn: 0,
+        };
+        assert_eq!(doubling.simulate(&[0], 10), 1024);
+
+        let model = FishModel {
+            reset: 4,
+            newborn: 5,
+        };
+        // The doubling model overflows a `u64` after about 60 days.
+        //
+        for (model, max_days) in [(doubling, 50), (model, 100)] {
+            for days in 0..=max_days {
+                assert_eq!(
+                    model.simulate_matrix(&input, days, &Exact),
+                    BigUint::from(model.simulate(&input, days))
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_example() {
-        let input = parse_input("3,4,3,1,2");
+        let input = parse_input("3,4,3,1,2").unwrap();
 
         let result = solve_part_1(&input);
         assert_eq!(result, 5934);
@@ -62,9 +277,54 @@ mod tests {
         assert_eq!(result, 26984457539);
     }
 
+    #[test]
+    fn test_simulate_matrix() {
+        let input = parse_input("3,4,3,1,2").unwrap();
+        let model = FishModel::default();
+
+        for days in 0..=256 {
+            let expected = model.simulate(&input, days);
+
+            assert_eq!(
+                model.simulate_matrix(&input, days, &Exact),
+                BigUint::from(expected)
+            );
+            assert_eq!(
+                model.simulate_matrix(&input, days, &Modulo(1_000_000_007)),
+                expected % 1_000_000_007
+            );
+        }
+    }
+
+    #[test]
+    fn test_simulate_matrix_large() {
+        let input = parse_input("3,4,3,1,2").unwrap();
+        let model = FishModel::default();
+
+        // Doesn't fit into a u64 anymore.
+        //
+        let exact = model.simulate_matrix(&input, 1000, &Exact);
+        assert!(exact > BigUint::from(u64::MAX));
+        assert_eq!(
+            model.simulate_matrix(&input, 1000, &Modulo(1_000_000_007)),
+            (exact % 1_000_000_007u64).try_into().unwrap()
+        );
+
+        // Only feasible with modular arithmetic.
+        //
+        let modulo = Modulo(1_000_000_007);
+        let matrix = model.transition_matrix(&modulo, 9);
+        let half = pow_matrix(&modulo, &matrix, 10u64.pow(12) / 2);
+        assert_eq!(
+            pow_matrix(&modulo, &matrix, 10u64.pow(12)),
+            mul_matrix(&modulo, &half, &half)
+        );
+    }
+
     #[test]
     fn test_real() {
-        let input = parse_input(include_str!("../input/2021/day6.txt"));
+        let input =
+            parse_input(include_str!("../input/2021/day6.txt")).unwrap();
 
         let result = solve_part_1(&input);
         assert_eq!(result, 388739);
@@ -72,4 +332,16 @@ mod tests {
         let result = solve_part_2(&input);
         assert_eq!(result, 1741362314973);
     }
+
+    #[test]
+    fn test_malformed() {
+        let error = parse_input("3,4,3,,2").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidNumber);
+        assert_eq!((error.line, error.column), (1, 7));
+
+        let error = parse_input("3,4\r,3,1,2").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidNumber);
+        assert_eq!((error.line, error.column), (1, 3));
+        assert_eq!(error.text, "4\r");
+    }
 }
@@ -1,63 +1,160 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use rayon::prelude::*;
-use std::str::FromStr;
+use crate::error::{parse_number, ParseError};
 
 #[aoc_generator(day7)]
-fn parse_input(input: &str) -> Vec<u32> {
+fn parse_input(input: &str) -> Result<Vec<u32>, ParseError> {
+    let input = input.trim();
+
     input
-        .trim()
         .split(',')
-        .flat_map(u32::from_str)
-        .collect::<Vec<_>>()
+        .map(|num| parse_number(0, input, num))
+        .collect()
+}
+
+/// The fuel a crab needs to move a number of steps.
+pub trait FuelCost {
+    fn cost(&self, distance: u32) -> u64;
+
+    /// Whether the cost is convex and non-decreasing in the distance. The
+    /// total cost is then convex in the position, which allows a ternary
+    /// search instead of trying every position.
+    fn is_convex(&self) -> bool { false }
+}
+
+/// Every step costs one fuel.
+pub struct Linear;
+
+impl FuelCost for Linear {
+    fn cost(&self, distance: u32) -> u64 { distance as u64 }
+
+    fn is_convex(&self) -> bool { true }
+}
+
+/// Every step costs one more fuel than the previous one.
+pub struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: u32) -> u64 {
+        let n = distance as u64;
+        (n * (n + 1)) / 2
+    }
+
+    fn is_convex(&self) -> bool { true }
+}
+
+/// The cost is the square of the distance.
+pub struct Quadratic;
+
+impl FuelCost for Quadratic {
+    fn cost(&self, distance: u32) -> u64 { distance as u64 * distance as u64 }
+
+    fn is_convex(&self) -> bool { true }
 }
 
+/// Marks a user supplied cost function as convex and non-decreasing.
+pub struct Convex<F>(pub F);
+
+impl<F: Fn(u32) -> u64> FuelCost for Convex<F> {
+    fn cost(&self, distance: u32) -> u64 { (self.0)(distance) }
+
+    fn is_convex(&self) -> bool { true }
+}
+
+/// Any closure can be used as cost function, but will be assumed to not be
+/// convex. Wrap it in [`Convex`] otherwise.
+impl<F: Fn(u32) -> u64> FuelCost for F {
+    fn cost(&self, distance: u32) -> u64 { self(distance) }
+}
+
+pub fn total_cost(input: &[u32], position: u32, cost: &impl FuelCost) -> u64 {
+    input
+        .iter()
+        .map(|input| cost.cost(input.abs_diff(position)))
+        .sum::<u64>()
+}
+
+/// Finds the position with the lowest total cost between the leftmost and
+/// rightmost crab, and returns it together with the cost.
+///
+/// Convex costs use a ternary search, all others try every position.
+pub fn optimal_position(input: &[u32], cost: &impl FuelCost) -> (u32, u64) {
+    let mut min = *input.iter().min().unwrap();
+    let mut max = *input.iter().max().unwrap();
+
+    if cost.is_convex() {
+        while max - min > 2 {
+            let left = min + (max - min) / 3;
+            let right = max - (max - min) / 3;
+
+            if total_cost(input, left, cost) <= total_cost(input, right, cost) {
+                max = right;
+            } else {
+                min = left;
+            }
+        }
+    }
+
+    (min..=max)
+        .map(|position| (position, total_cost(input, position, cost)))
+        .min_by_key(|&(_, cost)| cost)
+        .unwrap()
+}
+
+/// The linear cost is minimal at the median, since moving away from it
+/// moves away from at least half of the crabs.
 #[aoc(day7, part1)]
 fn solve_part_1(input: &[u32]) -> u32 {
-    let min = input.iter().min().unwrap();
-    let max = input.iter().max().unwrap();
+    let mut sorted = input.to_vec();
+    let (_, median, _) = sorted.select_nth_unstable(input.len() / 2);
 
-    (*min..*max)
-        .into_par_iter()
-        .map(|i| input.iter().map(|input| i.abs_diff(*input)).sum::<u32>())
-        .min()
-        .unwrap()
+    total_cost(input, *median, &Linear) as u32
 }
 
+/// The triangular cost is minimal within half a step of the mean, so only the
+/// positions around it have to be checked.
 #[aoc(day7, part2)]
 fn solve_part_2(input: &[u32]) -> u32 {
-    let min = input.iter().min().unwrap();
-    let max = input.iter().max().unwrap();
-
-    (*min..*max)
-        .into_par_iter()
-        .map(|i| {
-            input
-                .iter()
-                .map(|input| {
-                    let n = input.abs_diff(i);
-
-                    (n * (n + 1)) / 2
-                })
-                .sum::<u32>()
-        })
+    let sum = input.iter().map(|&n| n as u64).sum::<u64>();
+    let mean = (sum / input.len() as u64) as u32;
+
+    (mean.saturating_sub(1)..=mean + 1)
+        .map(|position| total_cost(input, position, &Triangular))
         .min()
-        .unwrap()
+        .unwrap() as u32
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ParseErrorKind;
+    use proptest::prelude::*;
+    use rayon::prelude::*;
+    use test::Bencher;
+
+    /// Tries every position between the leftmost and rightmost crab.
+    fn brute_force(input: &[u32], cost: &(impl FuelCost + Sync)) -> u64 {
+        let min = input.iter().min().unwrap();
+        let max = input.iter().max().unwrap();
+
+        (*min..=*max)
+            .into_par_iter()
+            .map(|i| total_cost(input, i, cost))
+            .min()
+            .unwrap()
+    }
 
     fn get_input() -> &'static str { "16,1,2,0,4,2,7,1,2,14" }
 
     #[test]
     fn test_parse_input() {
         assert_eq!(
-            parse_input(get_input()),
+            parse_input(get_input()).unwrap(),
             vec![16, 1, 2, 0, 4, 2, 7, 1, 2, 14]
         );
         assert_eq!(
-            parse_input(include_str!("../input/2021/day7.txt")).len(),
+            parse_input(include_str!("../input/2021/day7.txt"))
+                .unwrap()
+                .len(),
             1000
         );
     }
@@ -65,17 +162,118 @@ mod tests {
     #[test]
     fn test_example() {
         let input = get_input();
-        let input = parse_input(input);
+        let input = parse_input(input).unwrap();
 
         assert_eq!(solve_part_1(&input), 37);
         assert_eq!(solve_part_2(&input), 168);
+
+        assert_eq!(brute_force(&input, &Linear), 37);
+        assert_eq!(brute_force(&input, &Triangular), 168);
+    }
+
+    #[test]
+    fn test_single_crab() {
+        // The only candidate is the maximum, which the brute force used to
+        // skip.
+        //
+        assert_eq!(solve_part_1(&[5]), 0);
+        assert_eq!(solve_part_2(&[5]), 0);
+        assert_eq!(brute_force(&[5], &Linear), 0);
+        assert_eq!(brute_force(&[5], &Triangular), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn test_matches_brute_force(
+            input in prop::collection::vec(0u32..2000, 1..50)
+        ) {
+            prop_assert_eq!(
+                solve_part_1(&input) as u64,
+                brute_force(&input, &Linear)
+            );
+            prop_assert_eq!(
+                solve_part_2(&input) as u64,
+                brute_force(&input, &Triangular)
+            );
+        }
+
+        #[test]
+        fn test_optimal_position_matches_brute_force(
+            input in prop::collection::vec(0u32..2000, 1..50)
+        ) {
+            let (position, cost) = optimal_position(&input, &Linear);
+            prop_assert_eq!(cost, brute_force(&input, &Linear));
+            prop_assert_eq!(cost, total_cost(&input, position, &Linear));
+
+            let (_, cost) = optimal_position(&input, &Triangular);
+            prop_assert_eq!(cost, brute_force(&input, &Triangular));
+
+            let (_, cost) = optimal_position(&input, &Quadratic);
+            prop_assert_eq!(cost, brute_force(&input, &Quadratic));
+        }
+    }
+
+    #[test]
+    fn test_custom_cost() {
+        let input = parse_input(get_input()).unwrap();
+
+        assert_eq!(optimal_position(&input, &Linear), (2, 37));
+        assert_eq!(optimal_position(&input, &Triangular), (5, 168));
+
+        // Not convex, so every position has to be checked.
+        //
+        let alternating = |distance: u32| (distance % 2) as u64;
+        assert_eq!(optimal_position(&input, &alternating), (0, 3));
+        assert_eq!(brute_force(&input, &alternating), 3);
+
+        let cubic = Convex(|distance: u32| (distance as u64).pow(3));
+        assert_eq!(
+            optimal_position(&input, &cubic).1,
+            brute_force(&input, &cubic)
+        );
     }
 
     #[test]
     fn test_real() {
-        let input = parse_input(include_str!("../input/2021/day7.txt"));
+        let input =
+            parse_input(include_str!("../input/2021/day7.txt")).unwrap();
 
         assert_eq!(solve_part_1(&input), 344138);
         assert_eq!(solve_part_2(&input), 94862124);
+
+        assert_eq!(brute_force(&input, &Linear), 344138);
+        assert_eq!(brute_force(&input, &Triangular), 94862124);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let error = parse_input("16,1,2,0,4;2,7").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidNumber);
+        assert_eq!((error.line, error.column), (1, 10));
+        assert_eq!(error.text, "4;2");
+
+        let error = parse_input("").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidNumber);
+    }
+
+    #[bench]
+    fn bench_closed_form(b: &mut Bencher) {
+        let input =
+            parse_input(include_str!("../input/2021/day7.txt")).unwrap();
+
+        b.iter(|| (solve_part_1(&input), solve_part_2(&input)));
+    }
+
+    #[bench]
+    fn bench_brute_force(b: &mut Bencher) {
+        let input =
+            parse_input(include_str!("../input/2021/day7.txt")).unwrap();
+
+        b.iter(|| {
+            (
+                brute_force(&input, &Linear),
+                brute_force(&input, &Triangular),
+            )
+        });
     }
 }
@@ -0,0 +1,261 @@
+use aoc_runner_derive::{aoc, aoc_generator};
+use crate::error::{ParseError, ParseErrorKind};
+
+/// The segments of each digit on a regular display, where bit `i` is segment
+/// `'a' + i`.
+const DIGITS: [u8; 10] = [
+    0b1110111, // abcefg
+    0b0100100, // cf
+    0b1011101, // acdeg
+    0b1101101, // acdfg
+    0b0101110, // bcdf
+    0b1101011, // abdfg
+    0b1111011, // abdefg
+    0b0100101, // acf
+    0b1111111, // abcdefg
+    0b1101111, // abcdfg
+];
+
+/// A set of lit wires, where bit `i` is wire `'a' + i`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Pattern(u8);
+
+impl Pattern {
+    pub fn len(&self) -> u32 { self.0.count_ones() }
+
+    pub fn is_empty(&self) -> bool { self.0 == 0 }
+
+    pub fn contains(&self, wire: usize) -> bool { self.0 & (1 << wire) != 0 }
+
+    fn parse(
+        line_index: usize, line: &str, text: &str,
+    ) -> Result<Self, ParseError> {
+        text.char_indices().try_fold(Pattern(0), |pattern, (i, c)| match c {
+            'a'..='g' => Ok(Pattern(pattern.0 | 1 << (c as u8 - b'a'))),
+            _ => Err(ParseError::new(
+                ParseErrorKind::InvalidCharacter,
+                line_index,
+                line,
+                &text[i..i + c.len_utf8()],
+            )),
+        })
+    }
+}
+
+/// Maps each wire to the segment it is connected to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Wiring([u8; 7]);
+
+impl Wiring {
+    /// Deduces the wiring from the ten unique signal patterns.
+    ///
+    /// Over all ten digits, segments `b`, `e` and `f` are lit a unique number
+    /// of times (6, 4 and 9). Segments `a` and `c` are both lit 8 times, but
+    /// only `c` is part of the digit 1. Likewise `d` and `g` are both lit 7
+    /// times, but only `d` is part of the digit 4.
+    ///
+    /// Returns `None` if the patterns don't describe the ten digits.
+    pub fn deduce(patterns: &[Pattern; 10]) -> Option<Self> {
+        let one = patterns.iter().find(|p| p.len() == 2)?;
+        let four = patterns.iter().find(|p| p.len() == 4)?;
+
+        let mut wiring = [0u8; 7];
+        for (wire, segment) in wiring.iter_mut().enumerate() {
+            let count = patterns.iter().filter(|p| p.contains(wire)).count();
+
+            *segment = match count {
+                4 => b'e',
+                6 => b'b',
+                9 => b'f',
+                7 if four.contains(wire) => b'd',
+                7 => b'g',
+                8 if one.contains(wire) => b'c',
+                8 => b'a',
+                _ => return None,
+            } - b'a';
+        }
+
+        // Make sure that the patterns actually map onto all the digits.
+        //
+        let wiring = Wiring(wiring);
+        let mut seen = [false; 10];
+        for pattern in patterns {
+            let digit = wiring.decode(*pattern)?;
+            if std::mem::replace(&mut seen[digit as usize], true) {
+                return None;
+            }
+        }
+
+        Some(wiring)
+    }
+
+    /// The segment that the wire is connected to, e.g. `'d' -> 'a'`.
+    pub fn segment(&self, wire: char) -> Option<char> {
+        let index = (wire as u32).checked_sub('a' as u32)? as usize;
+        self.0.get(index).map(|&segment| (b'a' + segment) as char)
+    }
+
+    /// Translates the pattern to the digit it displays, if any.
+    pub fn decode(&self, pattern: Pattern) -> Option<u8> {
+        let segments = self
+            .0
+            .iter()
+            .enumerate()
+            .filter(|&(wire, _)| pattern.contains(wire))
+            .fold(0u8, |acc, (_, &segment)| acc | 1 << segment);
+
+        DIGITS
+            .iter()
+            .position(|&digit| digit == segments)
+            .map(|digit| digit as u8)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub patterns: [Pattern; 10],
+    pub output: [Pattern; 4],
+}
+
+impl Entry {
+    /// The four digit number shown on the display.
+    pub fn output_value(&self) -> Option<u32> {
+        let wiring = Wiring::deduce(&self.patterns)?;
+
+        self.output.iter().try_fold(0, |acc, &pattern| {
+            Some(acc * 10 + wiring.decode(pattern)? as u32)
+        })
+    }
+}
+
+fn parse_patterns<const N: usize>(
+    line_index: usize, line: &str, text: &str,
+) -> Result<[Pattern; N], ParseError> {
+    let mut patterns = [Pattern(0); N];
+
+    let parts = text.split_whitespace().collect::<Vec<_>>();
+    if parts.len() != N {
+        return Err(ParseError::new(
+            ParseErrorKind::UnexpectedLength {
+                expected: N,
+                found: parts.len(),
+            },
+            line_index,
+            line,
+            text,
+        ));
+    }
+
+    for (pattern, part) in patterns.iter_mut().zip(parts) {
+        *pattern = Pattern::parse(line_index, line, part)?;
+    }
+
+    Ok(patterns)
+}
+
+#[aoc_generator(day8)]
+fn parse_input(input: &str) -> Result<Vec<Entry>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let (patterns, output) = line.split_once(" | ").ok_or_else(|| {
+                ParseError::new(ParseErrorKind::MissingField, i, line, line)
+            })?;
+
+            Ok(Entry {
+                patterns: parse_patterns(i, line, patterns)?,
+                output: parse_patterns(i, line, output)?,
+            })
+        })
+        .collect()
+}
+
+#[aoc(day8, part1)]
+fn solve_part_1(input: &[Entry]) -> u32 {
+    // The digits 1, 7, 4 and 8 are the only ones with 2, 3, 4 and 7 segments.
+    //
+    input
+        .iter()
+        .flat_map(|entry| entry.output.iter())
+        .filter(|pattern| matches!(pattern.len(), 2 | 3 | 4 | 7))
+        .count() as u32
+}
+
+#[aoc(day8, part2)]
+fn solve_part_2(input: &[Entry]) -> Option<u32> {
+    input.iter().map(Entry::output_value).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    fn get_input() -> &'static str {
+        "be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
+edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc
+fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg
+fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb
+aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea
+fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb
+dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe
+bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef
+egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
+gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce"
+    }
+
+    #[test]
+    fn test_deduce() {
+        let input = parse_input(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | \
+             cdfeb fcadb cdfeb cdbaf",
+        )
+        .unwrap();
+        let wiring = Wiring::deduce(&input[0].patterns).unwrap();
+
+        let segments = "abcdefg"
+            .chars()
+            .map(|wire| wiring.segment(wire).unwrap())
+            .collect::<String>();
+        assert_eq!(segments, "cfgabde");
+        assert_eq!(input[0].output_value(), Some(5353));
+    }
+
+    #[test]
+    fn test_deduce_invalid() {
+        // Two identical patterns can't describe all ten digits.
+        //
+        let input = parse_input(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cdfbe ab | \
+             cdfeb fcadb cdfeb cdbaf",
+        )
+        .unwrap();
+        assert_eq!(Wiring::deduce(&input[0].patterns), None);
+    }
+
+    #[test]
+    fn test_example() {
+        let input = parse_input(get_input()).unwrap();
+
+        assert_eq!(solve_part_1(&input), 26);
+        assert_eq!(solve_part_2(&input), Some(61229));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let error = parse_input("ab cd | ef").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedLength {
+            expected: 10,
+            found: 2
+        });
+
+        let error = parse_input(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | \
+             cdfeb fcadb cdfeh cdbaf",
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidCharacter);
+        assert_eq!((error.line, error.column), (1, 78));
+    }
+}
@@ -1,121 +1,158 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use crate::{
+    error::ParseError,
+    grid::{Grid, Rgb, HIGHLIGHT_COLOUR},
+};
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::{collections::VecDeque, path::Path};
 
 #[aoc_generator(day9)]
-fn parse_input(input: &str) -> Vec<Vec<u32>> {
+fn parse_input(input: &str) -> Result<Grid<u32>, ParseError> {
+    Grid::parse_digits(input)
+}
+
+#[aoc(day9, part1)]
+fn solve_part_1(input: &Grid<u32>) -> u32 {
     input
-        .lines()
-        .map(|line| {
-            line.chars()
-                .into_iter()
-                .filter_map(|c| c.to_digit(10))
-                .collect::<Vec<_>>()
+        .positions()
+        .filter(|&pos| {
+            input.neighbours4(pos).all(|other| input[pos] < input[other])
         })
-        .collect::<Vec<_>>()
+        .map(|pos| input[pos] + 1)
+        .sum()
 }
 
-#[aoc(day9, part1)]
-fn solve_part_1(input: &[Vec<u32>]) -> u32 {
-    let mut min_num = Vec::new();
-    for row in 0..input.len() {
-        for col in 0..input[0].len() {
-            let current = input[row][col];
-
-            let up = if row.checked_sub(1).is_none() {
-                true
-            } else {
-                current < input[row - 1][col]
-            };
-            let down = if row + 1 == input.len() {
-                true
-            } else {
-                current < input[row + 1][col]
-            };
-            let left = if col.checked_sub(1).is_none() {
-                true
-            } else {
-                current < input[row][col - 1]
-            };
-            let right = if col + 1 >= input[0].len() {
-                true
-            } else {
-                current < input[row][col + 1]
-            };
-
-            if up && down && left && right {
-                min_num.push(current);
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Basin {
+    pub low_point: (usize, usize),
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Basin {
+    pub fn size(&self) -> usize { self.cells.len() }
+}
+
+pub struct Basins {
+    /// The index of the basin each cell belongs to, or `None` for the 9s.
+    pub labels: Grid<Option<usize>>,
+    pub basins: Vec<Basin>,
+}
+
+impl Basins {
+    /// The indices of the `n` largest basins, largest first.
+    pub fn largest(&self, n: usize) -> Vec<usize> {
+        (0..self.basins.len())
+            .sorted_by_key(|&i| std::cmp::Reverse(self.basins[i].size()))
+            .take(n)
+            .collect()
+    }
+}
+
+/// Labels the connected areas that aren't separated by 9s with a breadth
+/// first search.
+pub fn find_basins(input: &Grid<u32>) -> Basins {
+    let mut labels = Grid::new(input.width(), input.height(), None);
+    let mut basins = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for start in input.positions() {
+        if input[start] == 9 || labels[start].is_some() {
+            continue;
+        }
+
+        let label = basins.len();
+        let mut cells = Vec::new();
+
+        labels[start] = Some(label);
+        queue.push_back(start);
+        while let Some(current) = queue.pop_front() {
+            cells.push(current);
+
+            for neighbour in input.neighbours4(current) {
+                if input[neighbour] != 9 && labels[neighbour].is_none() {
+                    labels[neighbour] = Some(label);
+                    queue.push_back(neighbour);
+                }
             }
         }
+
+        let low_point = *cells.iter().min_by_key(|&&pos| input[pos]).unwrap();
+        basins.push(Basin { low_point, cells });
     }
 
-    min_num.into_iter().map(|n| n + 1).sum()
+    Basins { labels, basins }
 }
 
-fn flood_fill(
-    checked: &mut HashMap<(usize, usize), bool>, input: &[Vec<u32>],
-    result: &mut Vec<((usize, usize), u32)>, current: (usize, usize),
-) {
-    let (x, y) = current;
+/// Colours of the three largest basins.
+const LARGEST_COLOURS: [Rgb; 3] =
+    [(230, 60, 60), (60, 200, 80), (70, 110, 240)];
+const LOW_POINT_COLOUR: Rgb = HIGHLIGHT_COLOUR;
+const RIDGE_COLOUR: Rgb = (20, 20, 20);
 
-    // Return if we are at a 9
-    //
-    if input[x][y] == 9 {
-        return;
+/// The colour of a cell: low points are highlighted, the three largest basins
+/// get their own colour and all other basins are grey. Deeper cells are
+/// brighter.
+fn cell_colour(
+    input: &Grid<u32>, basins: &Basins, largest: &[usize], pos: (usize, usize),
+) -> Rgb {
+    let Some(label) = basins.labels[pos] else {
+        return RIDGE_COLOUR;
+    };
+    if basins.basins[label].low_point == pos {
+        return LOW_POINT_COLOUR;
     }
 
-    // Return if already checked
+    let (r, g, b) = largest
+        .iter()
+        .position(|&i| i == label)
+        .map_or((160, 160, 160), |i| LARGEST_COLOURS[i]);
+
+    // Scale from 100% at height 0 to 60% at height 8. Grids that weren't
+    // parsed from digits can be higher, so clamp them.
     //
-    if checked.contains_key(&current) {
-        return;
-    }
+    let height = input[pos].min(8);
+    let shade = |c: u8| (c as u32 * (20 - height) / 20) as u8;
+    (shade(r), shade(g), shade(b))
+}
 
-    checked.insert(current, true);
-    result.push(((x, y), input[x][y]));
+/// Renders the height map with ANSI colours, see [`cell_colour`].
+pub fn render_ansi(input: &Grid<u32>, basins: &Basins) -> String {
+    let largest = basins.largest(3);
+    input.render_ansi(|pos| {
+        (cell_colour(input, basins, &largest, pos), input[pos])
+    })
+}
 
-    if x.checked_sub(1).is_some() {
-        flood_fill(checked, input, result, (x - 1, y));
-    }
-    if x + 1 < input.len() {
-        flood_fill(checked, input, result, (x + 1, y));
-    }
+/// Renders the height map as binary PPM image, where every cell is a square
+/// of `scale` pixels.
+pub fn render_ppm(input: &Grid<u32>, basins: &Basins, scale: usize) -> Vec<u8> {
+    let largest = basins.largest(3);
+    input.render_ppm(scale, |pos| cell_colour(input, basins, &largest, pos))
+}
 
-    if y.checked_sub(1).is_some() {
-        flood_fill(checked, input, result, (x, y - 1));
-    }
-    if y + 1 < input[0].len() {
-        flood_fill(checked, input, result, (x, y + 1));
-    }
+pub fn write_ppm(
+    path: impl AsRef<Path>, input: &Grid<u32>, basins: &Basins, scale: usize,
+) -> std::io::Result<()> {
+    std::fs::write(path, render_ppm(input, basins, scale))
 }
 
 #[aoc(day9, part2)]
-fn solve_part_2(input: &[Vec<u32>]) -> u32 {
-    let mut all_results = Vec::new();
-    let mut checked = HashMap::new();
-
-    for row in 0..input.len() {
-        for col in 0..input[0].len() {
-            let mut result = Vec::new();
-            flood_fill(&mut checked, input, &mut result, (row, col));
-            all_results.push(result);
-        }
-    }
+fn solve_part_2(input: &Grid<u32>) -> u32 {
+    let basins = find_basins(input);
 
     // Find the 3 largest basins and return them.
     //
-    all_results
-        .iter()
-        .sorted_by_key(|r| r.len())
-        .rev()
-        .take(3)
-        .map(|n| n.len())
-        .reduce(|acc, val| acc * val)
-        .unwrap() as u32
+    basins
+        .largest(3)
+        .into_iter()
+        .map(|i| basins.basins[i].size())
+        .product::<usize>() as u32
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ParseErrorKind;
 
     fn get_input() -> &'static str {
         "2199943210\n3987894921\n9856789892\n8767896789\n9899965678"
@@ -124,18 +161,150 @@ mod tests {
     #[test]
     fn test_example() {
         let input = get_input();
-        let input = parse_input(input);
+        let input = parse_input(input).unwrap();
 
         assert_eq!(solve_part_1(&input), 15);
         assert_eq!(solve_part_2(&input), 1134);
     }
 
+    #[test]
+    fn test_find_basins() {
+        let input = parse_input(get_input()).unwrap();
+        let basins = find_basins(&input);
+
+        assert_eq!(basins.basins.len(), 4);
+        assert_eq!(
+            basins.basins.iter().map(Basin::size).collect::<Vec<_>>(),
+            [3, 9, 14, 9]
+        );
+        assert_eq!(
+            basins.basins.iter().map(|b| b.low_point).collect::<Vec<_>>(),
+            [(0, 1), (0, 9), (2, 2), (4, 6)]
+        );
+        assert_eq!(basins.labels[(0, 0)], Some(0));
+        assert_eq!(basins.labels[(0, 2)], None);
+        assert_eq!(basins.largest(3), [2, 1, 3]);
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let input = parse_input(get_input()).unwrap();
+        let output = render_ansi(&input, &find_basins(&input));
+
+        assert_eq!(output.lines().count(), 5);
+        assert!(output.lines().all(|line| line.ends_with("\x1b[0m")));
+
+        // The top left cell is part of a small basin, and the low point next
+        // to it is highlighted.
+        //
+        assert!(output.starts_with(
+            "\x1b[48;2;144;144;144m\x1b[38;2;0;0;0m2\x1b[48;2;255;230;0m"
+        ));
+    }
+
+    #[test]
+    fn test_render_ppm() {
+        let input = parse_input(get_input()).unwrap();
+        let basins = find_basins(&input);
+        let output = render_ppm(&input, &basins, 2);
+
+        let header = b"P6\n20 10\n255\n";
+        assert!(output.starts_with(header));
+        assert_eq!(output.len(), header.len() + 20 * 10 * 3);
+
+        let pixel = |x: usize, y: usize| {
+            let offset = header.len() + (y * 20 + x) * 3;
+            (output[offset], output[offset + 1], output[offset + 2])
+        };
+        assert_eq!(pixel(2, 0), LOW_POINT_COLOUR);
+        assert_eq!(pixel(3, 1), LOW_POINT_COLOUR);
+        assert_eq!(pixel(4, 0), RIDGE_COLOUR);
+
+        // The largest basin is around (2, 2), with a height of 6 at (2, 3).
+        //
+        let (r, g, b) = LARGEST_COLOURS[0];
+        let shade = |c: u8| (c as u32 * 14 / 20) as u8;
+        assert_eq!(pixel(6, 4), (shade(r), shade(g), shade(b)));
+    }
+
+    #[test]
+    fn test_render_high_cells() {
+        let input = Grid::from_vec(3, 1, vec![0, 8, 100]);
+        let basins = find_basins(&input);
+        let output = render_ppm(&input, &basins, 1);
+
+        // Heights above 8 get the same shade as 8.
+        //
+        let (r, g, b) = LARGEST_COLOURS[0];
+        let shade = |c: u8| (c as u32 * 12 / 20) as u8;
+        let pixel = [shade(r), shade(g), shade(b)];
+        let (r, g, b) = LOW_POINT_COLOUR;
+
+        let header = b"P6\n3 1\n255\n";
+        assert_eq!(output[header.len()..], [[r, g, b], pixel, pixel].concat());
+    }
+
+    #[test]
+    fn test_large_map() {
+        // A single basin spanning the whole map, which is too deep for a
+        // recursive flood fill.
+        //
+        let input = Grid::from_vec(
+            2000,
+            2000,
+            (0..2000 * 2000)
+                .map(|i| ((i / 2000 + i % 2000) % 9) as u32)
+                .collect(),
+        );
+        let basins = find_basins(&input);
+        assert_eq!(basins.basins.len(), 1);
+        assert_eq!(basins.basins[0].size(), 2000 * 2000);
+        assert_eq!(basins.basins[0].low_point, (0, 0));
+
+        // Walls of 9s on every 10th row and column.
+        //
+        let input = Grid::from_vec(
+            2000,
+            2000,
+            (0..2000 * 2000)
+                .map(|i| {
+                    let (row, col) = (i / 2000 % 10, i % 2000 % 10);
+                    if row == 9 || col == 9 {
+                        9
+                    } else {
+                        (row + col).min(8) as u32
+                    }
+                })
+                .collect(),
+        );
+        let basins = find_basins(&input);
+        assert_eq!(basins.basins.len(), 200 * 200);
+        assert!(basins.basins.iter().all(|basin| basin.size() == 81));
+        assert_eq!(basins.basins[1].low_point, (0, 10));
+        assert_eq!(solve_part_2(&input), 81 * 81 * 81);
+    }
+
     #[test]
     fn test_real() {
         let input = include_str!("../input/2021/day9.txt");
-        let input = parse_input(input);
+        let input = parse_input(input).unwrap();
 
         assert_eq!(solve_part_1(&input), 594);
         assert_eq!(solve_part_2(&input), 858494);
     }
+
+    #[test]
+    fn test_malformed() {
+        let error =
+            parse_input("2199943210\n398789492\n9856789892").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedLength {
+            expected: 10,
+            found: 9
+        });
+        assert_eq!(error.line, 2);
+
+        let error = parse_input("2199943210\n39878\r4921").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidDigit);
+        assert_eq!((error.line, error.column), (2, 6));
+    }
 }
@@ -0,0 +1,113 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    InvalidNumber,
+    InvalidDigit,
+    InvalidCharacter,
+    UnknownCommand,
+    MissingField,
+    UnexpectedToken,
+    UnexpectedLength { expected: usize, found: usize },
+    UnexpectedEnd,
+    /// A number that is valid, but larger than the generator allows.
+    LimitExceeded { limit: u64 },
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::InvalidNumber => write!(f, "invalid number"),
+            ParseErrorKind::InvalidDigit => write!(f, "invalid digit"),
+            ParseErrorKind::InvalidCharacter => write!(f, "invalid character"),
+            ParseErrorKind::UnknownCommand => write!(f, "unknown command"),
+            ParseErrorKind::MissingField => write!(f, "missing field"),
+            ParseErrorKind::UnexpectedToken => write!(f, "unexpected token"),
+            ParseErrorKind::UnexpectedLength { expected, found } => {
+                write!(f, "expected {} items, found {}", expected, found)
+            }
+            ParseErrorKind::UnexpectedEnd => {
+                write!(f, "unexpected end of input")
+            }
+            ParseErrorKind::LimitExceeded { limit } => {
+                write!(f, "exceeds the limit of {}", limit)
+            }
+        }
+    }
+}
+
+/// Error returned by the generators when the puzzle input is malformed.
+///
+/// Lines and columns are one-based, so they can be looked up directly in an
+/// editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+}
+
+impl ParseError {
+    /// Creates an error for `text`, which should be a slice of `line`. The
+    /// column is derived from the position of `text` within `line`, and
+    /// `line_index` is zero-based (as returned by `enumerate`).
+    pub fn new(
+        kind: ParseErrorKind, line_index: usize, line: &str, text: &str,
+    ) -> Self {
+        let offset = (text.as_ptr() as usize)
+            .saturating_sub(line.as_ptr() as usize)
+            .min(line.len());
+
+        Self {
+            kind,
+            line: line_index + 1,
+            column: line.get(..offset).map_or(0, |s| s.chars().count()) + 1,
+            text: text.to_string(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {} `{}`",
+            self.line, self.column, self.kind, self.text
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parses `text` (a slice of `line`) as a number.
+pub fn parse_number<T: FromStr>(
+    line_index: usize, line: &str, text: &str,
+) -> Result<T, ParseError> {
+    T::from_str(text).map_err(|_| {
+        ParseError::new(ParseErrorKind::InvalidNumber, line_index, line, text)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column() {
+        let line = "forward 5x";
+        let error = parse_number::<u32>(2, line, &line[8..]).unwrap_err();
+
+        assert_eq!(error.line, 3);
+        assert_eq!(error.column, 9);
+        assert_eq!(error.text, "5x");
+        assert_eq!(
+            error.to_string(),
+            "line 3, column 9: invalid number `5x`"
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use crate::error::{ParseError, ParseErrorKind};
+use std::{
+    fmt::{Display, Formatter},
+    ops::{Index, IndexMut},
+};
+
+/// Offsets of the orthogonally adjacent cells (up, left, right, down).
+const ORTHOGONAL: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+/// Offsets of all adjacent cells, including the diagonals.
+const ADJACENT: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+/// A colour as red, green and blue components.
+pub type Rgb = (u8, u8, u8);
+
+/// Colour used by the renderers to make special cells stand out.
+pub const HIGHLIGHT_COLOUR: Rgb = (255, 230, 0);
+
+/// A two dimensional grid stored in a flat, row-major buffer. Positions are
+/// `(row, column)` tuples.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self
+    where T: Clone {
+        Self {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+
+    /// Creates a grid from a row-major buffer.
+    ///
+    /// Panics if the buffer doesn't contain exactly `width * height` cells.
+    pub fn from_vec(width: usize, height: usize, cells: Vec<T>) -> Self {
+        assert_eq!(cells.len(), width * height);
+
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    pub fn width(&self) -> usize { self.width }
+
+    pub fn height(&self) -> usize { self.height }
+
+    pub fn len(&self) -> usize { self.cells.len() }
+
+    pub fn is_empty(&self) -> bool { self.cells.is_empty() }
+
+    pub fn contains(&self, pos: (usize, usize)) -> bool {
+        pos.0 < self.height && pos.1 < self.width
+    }
+
+    pub fn get(&self, pos: (usize, usize)) -> Option<&T> {
+        self.contains(pos).then(|| &self.cells[self.index_of(pos)])
+    }
+
+    pub fn get_mut(&mut self, pos: (usize, usize)) -> Option<&mut T> {
+        if self.contains(pos) {
+            let index = self.index_of(pos);
+            Some(&mut self.cells[index])
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> { self.cells.iter() }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cells.iter_mut()
+    }
+
+    /// All positions of the grid in row-major order.
+    pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> {
+        let width = self.width;
+        (0..self.height)
+            .flat_map(move |row| (0..width).map(move |col| (row, col)))
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // `chunks` panics on a chunk size of zero, so guard against empty rows.
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn column(&self, col: usize) -> impl Iterator<Item = &T> {
+        assert!(col < self.width);
+        self.cells.iter().skip(col).step_by(self.width)
+    }
+
+    /// The orthogonal neighbours of the position that are inside the grid.
+    ///
+    /// The iterator doesn't borrow the grid, so it can be modified while
+    /// walking the neighbours.
+    pub fn neighbours4(
+        &self, pos: (usize, usize),
+    ) -> impl Iterator<Item = (usize, usize)> {
+        self.offset_positions(pos, &ORTHOGONAL)
+    }
+
+    /// The orthogonal and diagonal neighbours of the position that are inside
+    /// the grid.
+    pub fn neighbours8(
+        &self, pos: (usize, usize),
+    ) -> impl Iterator<Item = (usize, usize)> {
+        self.offset_positions(pos, &ADJACENT)
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    /// Renders the grid for a terminal with 24-bit ANSI colours. `cell`
+    /// returns the background colour and the text of each position, which is
+    /// drawn in black.
+    pub fn render_ansi<S: Display>(
+        &self, mut cell: impl FnMut((usize, usize)) -> (Rgb, S),
+    ) -> String {
+        let mut output = String::new();
+
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let ((r, g, b), text) = cell((row, col));
+                output += &format!(
+                    "\x1b[48;2;{};{};{}m\x1b[38;2;0;0;0m{}",
+                    r, g, b, text
+                );
+            }
+            output += "\x1b[0m\n";
+        }
+
+        output
+    }
+
+    /// Renders the grid as binary PPM image, where every cell is a square of
+    /// `scale` pixels with the colour returned by `colour`.
+    pub fn render_ppm(
+        &self, scale: usize, mut colour: impl FnMut((usize, usize)) -> Rgb,
+    ) -> Vec<u8> {
+        let (width, height) = (self.width * scale, self.height * scale);
+
+        let mut output =
+            format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        for y in 0..height {
+            for x in 0..width {
+                let (r, g, b) = colour((y / scale, x / scale));
+                output.extend([r, g, b]);
+            }
+        }
+
+        output
+    }
+
+    fn index_of(&self, pos: (usize, usize)) -> usize {
+        pos.0 * self.width + pos.1
+    }
+
+    fn offset_positions(
+        &self, pos: (usize, usize), offsets: &'static [(isize, isize)],
+    ) -> impl Iterator<Item = (usize, usize)> {
+        let (height, width) = (self.height as isize, self.width as isize);
+
+        offsets.iter().filter_map(move |&(dr, dc)| {
+            let row = pos.0 as isize + dr;
+            let col = pos.1 as isize + dc;
+
+            (row >= 0 && row < height && col >= 0 && col < width)
+                .then_some((row as usize, col as usize))
+        })
+    }
+}
+
+impl<T: From<u8>> Grid<T> {
+    /// Parses a map of single digits such as `"123\n456"`. All rows must have
+    /// the same length.
+    pub fn parse_digits(input: &str) -> Result<Self, ParseError> {
+        let mut width = None;
+        let mut height = 0;
+        let mut cells = Vec::new();
+
+        for (i, line) in input.lines().enumerate() {
+            for (j, c) in line.char_indices() {
+                let digit = c.to_digit(10).ok_or_else(|| {
+                    ParseError::new(
+                        ParseErrorKind::InvalidDigit,
+                        i,
+                        line,
+                        &line[j..j + c.len_utf8()],
+                    )
+                })?;
+                cells.push(T::from(digit as u8));
+            }
+
+            let row_width = line.chars().count();
+            let expected = *width.get_or_insert(row_width);
+            if row_width != expected {
+                return Err(ParseError::new(
+                    ParseErrorKind::UnexpectedLength {
+                        expected,
+                        found: row_width,
+                    },
+                    i,
+                    line,
+                    line,
+                ));
+            }
+            height += 1;
+        }
+
+        Ok(Self::from_vec(width.unwrap_or_default(), height, cells))
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, pos: (usize, usize)) -> &Self::Output {
+        assert!(self.contains(pos), "{:?} is out of bounds", pos);
+        &self.cells[self.index_of(pos)]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, pos: (usize, usize)) -> &mut Self::Output {
+        assert!(self.contains(pos), "{:?} is out of bounds", pos);
+        let index = self.index_of(pos);
+        &mut self.cells[index]
+    }
+}
+
+impl<T: Display> Display for Grid<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_input() -> &'static str { "123\n456\n789" }
+
+    #[test]
+    fn test_parse_digits() {
+        let grid = Grid::<u32>::parse_digits(get_input()).unwrap();
+
+        assert_eq!(grid.width(), 3);
+        assert_eq!(grid.height(), 3);
+        assert_eq!(grid[(1, 2)], 6);
+        assert_eq!(grid.get((3, 0)), None);
+        assert_eq!(grid.to_string(), "123\n456\n789\n");
+    }
+
+    #[test]
+    fn test_rows_and_columns() {
+        let grid = Grid::<u32>::parse_digits(get_input()).unwrap();
+
+        assert_eq!(grid.row(1), [4, 5, 6]);
+        assert_eq!(grid.column(2).copied().collect::<Vec<_>>(), [3, 6, 9]);
+        assert_eq!(grid.rows().count(), 3);
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let grid = Grid::<u32>::parse_digits(get_input()).unwrap();
+
+        assert_eq!(grid.neighbours4((0, 0)).collect::<Vec<_>>(), [
+            (0, 1),
+            (1, 0)
+        ]);
+        assert_eq!(grid.neighbours4((1, 1)).count(), 4);
+        assert_eq!(grid.neighbours8((0, 0)).collect::<Vec<_>>(), [
+            (0, 1),
+            (1, 0),
+            (1, 1)
+        ]);
+        assert_eq!(grid.neighbours8((1, 1)).count(), 8);
+        assert_eq!(grid.neighbours8((2, 2)).count(), 3);
+    }
+
+    #[test]
+    fn test_render() {
+        let grid = Grid::<u32>::parse_digits("12\n34").unwrap();
+        let colour = |pos: (usize, usize)| (pos.0 as u8, pos.1 as u8, 7);
+
+        let output = grid.render_ansi(|pos| (colour(pos), grid[pos]));
+        assert_eq!(
+            output,
+            "\x1b[48;2;0;0;7m\x1b[38;2;0;0;0m1\x1b[48;2;0;1;7m\x1b[38;2;0;0;0m2\
+             \x1b[0m\n\x1b[48;2;1;0;7m\x1b[38;2;0;0;0m3\
+             \x1b[48;2;1;1;7m\x1b[38;2;0;0;0m4\x1b[0m\n"
+        );
+
+        let output = grid.render_ppm(2, colour);
+        let header = b"P6\n4 4\n255\n";
+        assert_eq!(&output[..header.len()], header);
+        assert_eq!(output.len(), header.len() + 4 * 4 * 3);
+
+        let pixel = |x: usize, y: usize| {
+            &output[header.len() + (y * 4 + x) * 3..][..3]
+        };
+        assert_eq!(pixel(1, 1), [0, 0, 7]);
+        assert_eq!(pixel(2, 1), [0, 1, 7]);
+        assert_eq!(pixel(3, 3), [1, 1, 7]);
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let error = Grid::<u32>::parse_digits("123\n4a6").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidDigit);
+        assert_eq!((error.line, error.column), (2, 2));
+
+        let error = Grid::<u32>::parse_digits("123\n45\n789").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedLength {
+            expected: 3,
+            found: 2
+        });
+        assert_eq!(error.line, 2);
+    }
+}
@@ -3,6 +3,9 @@
 #![feature(int_abs_diff)]
 #![feature(decl_macro)]
 #![feature(let_else)]
+#![cfg_attr(test, feature(test))]
+
+#[cfg(test)] extern crate test;
 
 use aoc_runner_derive::aoc_lib;
 
@@ -15,7 +18,10 @@ pub mod day4;
 pub mod day5;
 pub mod day6;
 pub mod day7;
+pub mod day8;
 pub mod day9;
+pub mod error;
+pub mod grid;
 
 #[cfg(target_env = "DONT_COMPILE_THIS")] pub mod template;
 